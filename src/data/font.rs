@@ -1,21 +1,28 @@
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::fs;
 use std::path::Path;
-use sdl2::ttf::Font;
-use crate::{Data, Resources};
+use std::rc::Rc;
+use sdl2::rect::Rect;
+use crate::{Data, Error, Resources};
+use crate::data::gfx::TextureData;
 use crate::data::join_as_string;
-use crate::resources::LoadResult;
+use crate::gfx::font::{BitmapFont, Glyph, TextFont};
+use crate::point::IntPoint;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum FontData {
     Inline { path: String, size: u16 },
+    Bitmap { source: BitmapFontData },
     Ref { id: String },
 }
 
 impl FontData {
-    pub fn load<'ttf>(&self, resources: &mut dyn Resources<'ttf>) -> LoadResult<Font<'ttf, 'static>> {
+    pub fn load<'ttf>(&self, resources: &mut dyn Resources<'ttf>) -> Result<Rc<dyn TextFont<'ttf> + 'ttf>, Error> {
         match self {
             FontData::Inline { path, size } => Ok(resources.load_font(path.as_ref(), *size)?),
-            FontData::Ref { id } => resources.get_font(id),
+            FontData::Bitmap { source } => Ok(Rc::new(source.load(resources)?)),
+            FontData::Ref { id } => Ok(resources.get_font(id)?),
         }
     }
 }
@@ -26,7 +33,247 @@ impl Data for FontData {
             FontData::Inline { path , ..} => {
                 *path = join_as_string(base_path, &path);
             },
+            FontData::Bitmap { source } => source.reroot(base_path),
             FontData::Ref{ .. } => {},
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KerningData {
+    pub first: char,
+    pub second: char,
+    pub amount: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BitmapFontData {
+    // AngelCode BMFont descriptor in text format, page images are relative to it
+    BmFont { path: String },
+    // Fixed size glyphs laid out row by row in the same order as `characters`
+    Grid {
+        texture: TextureData,
+        glyph_width: u32,
+        glyph_height: u32,
+        characters: String,
+        #[serde(default)]
+        kerning: Vec<KerningData>,
+    },
+}
+
+impl BitmapFontData {
+    pub fn load<'ttf>(&self, resources: &mut dyn Resources<'ttf>) -> Result<BitmapFont<'ttf>, Error> {
+        match self {
+            BitmapFontData::BmFont { path } => load_bmfont(Path::new(path), resources).map_err(|e| e.with_path(path)),
+            BitmapFontData::Grid { texture, glyph_width, glyph_height, characters, kerning } => {
+                if *glyph_width == 0 || *glyph_height == 0 {
+                    return Err(Error::simple(format!("Glyphs of {}x{} can not be laid out", glyph_width, glyph_height)));
+                }
+                let texture = texture.load(resources)?;
+                let columns = (texture.width() / glyph_width).max(1);
+                let glyphs = characters.chars().enumerate()
+                    .map(|(index, character)| {
+                        let index = index as u32;
+                        let rect = Rect::new(((index % columns) * glyph_width) as i32, ((index / columns) * glyph_height) as i32, *glyph_width, *glyph_height);
+                        (character, Glyph { page: 0, rect: Some(rect), offset: IntPoint::new(0, 0), advance: *glyph_width as i32 })
+                    })
+                    .collect();
+                let kerning = kerning.iter().map(|k| ((k.first, k.second), k.amount)).collect();
+                Ok(BitmapFont::new(vec![texture], glyphs, kerning, *glyph_height))
+            }
+        }
+    }
+}
+
+impl Data for BitmapFontData {
+    fn reroot(&mut self, base_path: &Path) {
+        match self {
+            BitmapFontData::BmFont { path } => {
+                *path = join_as_string(base_path, &path);
+            },
+            BitmapFontData::Grid { texture, .. } => texture.reroot(base_path),
+        }
+    }
+}
+
+// Splits a BMFont line into its tag and key=value pairs, values may be quoted
+fn parse_bmfont_line(line: &str) -> (&str, HashMap<&str, &str>) {
+    let line = line.trim();
+    let (tag, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+    let mut values = HashMap::new();
+    loop {
+        rest = rest.trim_start();
+        let (key, after_key) = match rest.split_once('=') {
+            Some(pair) => pair,
+            None => break,
+        };
+        let (value, remaining) = match after_key.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => after_key.split_once(' ').unwrap_or((after_key, "")),
+        };
+        values.insert(key.trim(), value);
+        rest = remaining;
+    }
+    (tag, values)
+}
+
+fn bmfont_value<V: std::str::FromStr>(values: &HashMap<&str, &str>, key: &str) -> Result<V, Error> {
+    values.get(key)
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| Error::simple(format!("Missing or invalid BMFont value '{}'", key)))
+}
+
+fn bmfont_char(values: &HashMap<&str, &str>, key: &str) -> Result<char, Error> {
+    char::from_u32(bmfont_value(values, key)?).ok_or_else(|| Error::simple(format!("Invalid BMFont character in '{}'", key)))
+}
+
+// Contents of a BMFont descriptor, before its pages are loaded
+struct BmFontDescriptor {
+    line_height: u32,
+    // Page files ordered by id
+    pages: Vec<String>,
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), i32>,
+}
+
+fn parse_bmfont(contents: &str) -> Result<BmFontDescriptor, Error> {
+    let mut line_height = 0;
+    let mut page_files: Vec<(usize, String)> = Vec::new();
+    let mut glyphs = HashMap::new();
+    let mut kerning = HashMap::new();
+
+    for line in contents.lines() {
+        let (tag, values) = parse_bmfont_line(line);
+        match tag {
+            "common" => line_height = bmfont_value(&values, "lineHeight")?,
+            "page" => page_files.push((bmfont_value(&values, "id")?, bmfont_value(&values, "file")?)),
+            "char" => {
+                let width: u32 = bmfont_value(&values, "width")?;
+                let height: u32 = bmfont_value(&values, "height")?;
+                let rect = if width > 0 && height > 0 {
+                    Some(Rect::new(bmfont_value(&values, "x")?, bmfont_value(&values, "y")?, width, height))
+                } else {
+                    None
+                };
+                glyphs.insert(bmfont_char(&values, "id")?, Glyph {
+                    page: bmfont_value(&values, "page")?,
+                    rect,
+                    offset: IntPoint::new(bmfont_value(&values, "xoffset")?, bmfont_value(&values, "yoffset")?),
+                    advance: bmfont_value(&values, "xadvance")?,
+                });
+            }
+            "kerning" => {
+                kerning.insert((bmfont_char(&values, "first")?, bmfont_char(&values, "second")?), bmfont_value(&values, "amount")?);
+            }
+            _ => {}
+        }
+    }
+
+    page_files.sort_by_key(|(id, _)| *id);
+    let pages = page_files.into_iter().map(|(_, file)| file).collect();
+    Ok(BmFontDescriptor { line_height, pages, glyphs, kerning })
+}
+
+fn load_bmfont<'ttf>(path: &Path, resources: &mut dyn Resources<'ttf>) -> Result<BitmapFont<'ttf>, Error> {
+    let descriptor = parse_bmfont(&fs::read_to_string(path)?)?;
+    let base_path = path.parent().unwrap_or_else(|| Path::new(""));
+    let pages = descriptor.pages.iter()
+        .map(|file| resources.load_texture(&base_path.join(file)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(BitmapFont::new(pages, descriptor.glyphs, descriptor.kerning, descriptor.line_height))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resources::NullResources;
+
+    use super::*;
+
+    const DESCRIPTOR: &str = r#"info face="Pixel Sans" size=16 bold=0
+common lineHeight=18 base=14 scaleW=128 scaleH=128 pages=2
+page id=1 file="pixel_1.png"
+page id=0 file="pixel_0.png"
+chars count=3
+char id=65   x=0 y=0 width=8 height=10 xoffset=0 yoffset=4 xadvance=9 page=0 chnl=15
+char id=86   x=8 y=0 width=8 height=10 xoffset=-1 yoffset=4 xadvance=8 page=1 chnl=15
+char id=32   x=0 y=0 width=0 height=0 xoffset=0 yoffset=0 xadvance=4 page=0 chnl=15
+kernings count=2
+kerning first=65 second=86 amount=-2
+kerning first=86 second=65 amount=-1
+"#;
+
+    #[test]
+    fn bmfont_lines() {
+        let (tag, values) = parse_bmfont_line(r#"info face="Pixel Sans" size=16  bold=0"#);
+        assert_eq!(tag, "info");
+        assert_eq!(values["face"], "Pixel Sans");
+        assert_eq!(values["size"], "16");
+        assert_eq!(values["bold"], "0");
+
+        // Tag alone, surrounding blanks, unterminated quotes and missing values
+        assert_eq!(parse_bmfont_line("  chars  "), ("chars", HashMap::new()));
+        assert_eq!(parse_bmfont_line(""), ("", HashMap::new()));
+        assert_eq!(parse_bmfont_line(r#"page id=0 file="open.png"#).1["file"], "open.png");
+        let (_, values) = parse_bmfont_line("char id= x=3 width");
+        assert_eq!(values["id"], "");
+        assert_eq!(values["x"], "3");
+        assert!(!values.contains_key("width"));
+    }
+
+    #[test]
+    fn bmfont_descriptor() {
+        let descriptor = parse_bmfont(DESCRIPTOR).unwrap();
+        assert_eq!(descriptor.line_height, 18);
+        assert_eq!(descriptor.pages, vec!["pixel_0.png", "pixel_1.png"]);
+        let v = descriptor.glyphs[&'V'];
+        assert_eq!((v.page, v.rect, v.offset, v.advance), (1, Some(Rect::new(8, 0, 8, 10)), IntPoint::new(-1, 4), 8));
+        assert_eq!(descriptor.glyphs[&' '].rect, None);
+        assert_eq!(descriptor.kerning, HashMap::from([(('A', 'V'), -2), (('V', 'A'), -1)]));
+    }
+
+    #[test]
+    fn sample_bmfont() {
+        let descriptor = parse_bmfont(&fs::read_to_string("data/CollegiateFLF.fnt").unwrap()).unwrap();
+        assert_eq!(descriptor.pages, vec!["CollegiateFLF_0.png"]);
+        assert!(('A'..='Z').all(|character| descriptor.glyphs[&character].rect.is_some()));
+        assert!(!descriptor.kerning.is_empty());
+    }
+
+    #[test]
+    fn empty_grid_glyphs() {
+        for (glyph_width, glyph_height) in [(0, 16), (16, 0)] {
+            let grid = BitmapFontData::Grid {
+                texture: TextureData::Inline { path: "font.png".to_owned() },
+                glyph_width,
+                glyph_height,
+                characters: "AB".to_owned(),
+                kerning: Vec::new(),
+            };
+            // Refused before the texture is even looked for
+            let error = grid.load(&mut NullResources {}).err().unwrap();
+            assert!(error.to_string().contains("can not be laid out"), "{}", error.to_string());
+        }
+    }
+
+    #[test]
+    fn malformed_bmfonts() {
+        let broken = [
+            // Missing and invalid values
+            "char id=65 x=0 y=0 width=8 xoffset=0 yoffset=0 xadvance=8 page=0",
+            "char id=65 x=0 y=0 width=8 height=ten xoffset=0 yoffset=0 xadvance=8 page=0",
+            "common base=14",
+            "page id=0",
+            // Kerning pairs need both characters and an amount
+            "kerning first=65 amount=-2",
+            "kerning first=65 second=86",
+            "kerning first=65 second=86 amount=much",
+            // Not a character
+            "kerning first=55296 second=86 amount=-2",
+        ];
+        for line in broken.iter() {
+            assert!(parse_bmfont(line).is_err(), "{}", line);
+        }
+        // Unknown lines are skipped
+        assert!(parse_bmfont("extra stuff=1\nnonsense").is_ok());
+    }
+}
@@ -1,13 +1,25 @@
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 use std::path::Path;
 use std::rc::Rc;
-use crate::{Data, Resources, SpriteSheet};
+use sdl2::image::LoadSurface;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
+use sdl2::surface::Surface;
+use crate::{Data, Error, Point, Resources, SpriteSheet};
 use crate::data::join_as_string;
+use crate::gfx::capture::FrameCapture;
+use crate::gfx::minimap;
+use crate::gfx::particles::EmitterConfig;
+use crate::gfx::plane::Plane;
 use crate::gfx::texture::Texture;
 use crate::gfx::tileset::Tileset;
+use crate::gfx::window::{FillMode, NineSlice, WindowSkin};
+use crate::point::FloatPoint;
 use crate::resources::LoadResult;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TextureData {
     Inline { path: String },
     Ref { id: String },
@@ -20,6 +32,14 @@ impl TextureData {
             TextureData::Ref { id } => resources.get_texture(id),
         }
     }
+
+    // CPU side copy of the image, for when pixels need to be inspected
+    pub fn load_surface(&self) -> Result<Surface<'static>, Error> {
+        match self {
+            TextureData::Inline { path } => Surface::from_file(path).map_err(|e| Error::simple(e).with_path(path)),
+            TextureData::Ref { .. } => Err(Error::simple("Resource ids are not supported")),
+        }
+    }
 }
 
 impl Data for TextureData {
@@ -33,7 +53,7 @@ impl Data for TextureData {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TilesetData {
     Inline { texture: TextureData, width: u32, height: u32 },
     Ref { id: String },
@@ -52,6 +72,13 @@ impl TilesetData {
             }
         }
     }
+
+    pub fn sample_tile_colors(&self) -> Result<HashMap<u32, Color>, Error> {
+        match self {
+            TilesetData::Inline { texture, width, height } => minimap::sample_tile_colors(&texture.load_surface()?, *width, *height),
+            TilesetData::Ref { .. } => Err(Error::simple("Resource ids are not supported")),
+        }
+    }
 }
 
 impl Data for TilesetData {
@@ -62,3 +89,249 @@ impl Data for TilesetData {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SpriteSheetData {
+    Inline { texture: TextureData, width: u32, height: u32 },
+    Ref { id: String },
+}
+
+impl SpriteSheetData {
+    pub fn load<'ttf>(&self, resources: &mut dyn Resources<'ttf>) -> LoadResult<SpriteSheet<'ttf>> {
+        match self {
+            SpriteSheetData::Inline { texture, width, height } => {
+                let texture = texture.load(resources)?;
+                Ok(Rc::new(SpriteSheet::new(texture, *width, *height)))
+            }
+            SpriteSheetData::Ref { id } => resources.get_sprite_sheet(id),
+        }
+    }
+}
+
+impl Data for SpriteSheetData {
+    fn reroot(&mut self, base_path: &Path) {
+        match self {
+            SpriteSheetData::Inline { texture, .. } => texture.reroot(base_path),
+            SpriteSheetData::Ref { .. } => {},
+        }
+    }
+}
+
+fn opaque() -> u8 {
+    255
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ColorData {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    #[serde(default = "opaque")]
+    pub a: u8,
+}
+
+impl ColorData {
+    pub fn to_color(&self) -> Color {
+        Color::RGBA(self.r, self.g, self.b, self.a)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RangeData {
+    pub min: f32,
+    pub max: f32,
+}
+
+// Lifetimes are in ticks (ms), velocities in pixels per second and gravity in pixels per second squared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmitterData {
+    pub sheet: SpriteSheetData,
+    pub frames: Vec<u32>,
+    pub spawn_rate: f32,
+    pub max_particles: usize,
+    pub lifetime: RangeData,
+    pub velocity_x: RangeData,
+    pub velocity_y: RangeData,
+    pub gravity: Point<f32>,
+    pub start_color: ColorData,
+    pub end_color: ColorData,
+}
+
+impl EmitterData {
+    pub fn load<'ttf>(&self, resources: &mut dyn Resources<'ttf>) -> LoadResult<EmitterConfig<'ttf>> {
+        Ok(Rc::new(EmitterConfig {
+            sheet: self.sheet.load(resources)?,
+            frames: self.frames.clone(),
+            spawn_rate: self.spawn_rate,
+            max_particles: self.max_particles,
+            lifetime: self.lifetime,
+            velocity_x: self.velocity_x,
+            velocity_y: self.velocity_y,
+            gravity: self.gravity,
+            start_color: self.start_color.to_color(),
+            end_color: self.end_color.to_color(),
+        }))
+    }
+}
+
+impl Data for EmitterData {
+    fn reroot(&mut self, base_path: &Path) {
+        self.sheet.reroot(base_path);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RectData {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RectData {
+    pub fn to_rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+fn stretch() -> FillMode {
+    FillMode::Stretch
+}
+
+fn default_background_inset() -> u32 {
+    2
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum WindowSkinData {
+    Inline {
+        texture: TextureData,
+        background: RectData,
+        #[serde(default = "stretch")]
+        background_fill: FillMode,
+        #[serde(default = "default_background_inset")]
+        background_inset: u32,
+        frame: RectData,
+        frame_border: u32,
+        cursor: RectData,
+        cursor_border: u32,
+    },
+    // Standard 192x128 RPG Maker XP layout
+    RpgMaker { texture: TextureData },
+    Ref { id: String },
+}
+
+impl WindowSkinData {
+    pub fn load<'ttf>(&self, resources: &mut dyn Resources<'ttf>) -> LoadResult<WindowSkin<'ttf>> {
+        match self {
+            WindowSkinData::Inline { texture, background, background_fill, background_inset, frame, frame_border, cursor, cursor_border } => {
+                let texture = texture.load(resources)?;
+                Ok(Rc::new(WindowSkin::new(
+                    texture.clone(),
+                    background.to_rect(),
+                    *background_fill,
+                    *background_inset,
+                    NineSlice::new(texture.clone(), frame.to_rect(), *frame_border),
+                    NineSlice::new(texture, cursor.to_rect(), *cursor_border),
+                )))
+            }
+            WindowSkinData::RpgMaker { texture } => {
+                let texture = texture.load(resources)?;
+                Ok(Rc::new(WindowSkin::new(
+                    texture.clone(),
+                    Rect::new(0, 0, 128, 128),
+                    FillMode::Stretch,
+                    2,
+                    NineSlice::new(texture.clone(), Rect::new(128, 0, 64, 64), 16),
+                    NineSlice::new(texture, Rect::new(128, 64, 32, 32), 2),
+                )))
+            }
+            WindowSkinData::Ref { id } => resources.get_window_skin(id),
+        }
+    }
+}
+
+impl Data for WindowSkinData {
+    fn reroot(&mut self, base_path: &Path) {
+        match self {
+            WindowSkinData::Inline { texture, .. } | WindowSkinData::RpgMaker { texture } => texture.reroot(base_path),
+            WindowSkinData::Ref { .. } => {},
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaptureData {
+    pub directory: String,
+    pub sequence_interval: u32,
+}
+
+impl Default for CaptureData {
+    fn default() -> Self {
+        CaptureData { directory: "captures".to_owned(), sequence_interval: 2 }
+    }
+}
+
+impl CaptureData {
+    pub fn to_frame_capture(&self) -> FrameCapture {
+        FrameCapture::new(&self.directory, self.sequence_interval)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum BlendModeData {
+    #[default]
+    Normal,
+    Add,
+    Multiply,
+}
+
+impl BlendModeData {
+    pub fn to_blend_mode(&self) -> BlendMode {
+        match self {
+            BlendModeData::Normal => BlendMode::Blend,
+            BlendModeData::Add => BlendMode::Add,
+            BlendModeData::Multiply => BlendMode::Mod,
+        }
+    }
+}
+
+fn fixed_to_map() -> FloatPoint {
+    FloatPoint::new(1., 1.)
+}
+
+fn not_moving() -> FloatPoint {
+    FloatPoint::new(0., 0.)
+}
+
+fn tiled() -> bool {
+    true
+}
+
+// Velocity is in pixels per second
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaneData {
+    pub texture: TextureData,
+    #[serde(default = "fixed_to_map")]
+    pub scroll_factor: FloatPoint,
+    #[serde(default = "not_moving")]
+    pub velocity: FloatPoint,
+    #[serde(default = "tiled")]
+    pub tiled: bool,
+    #[serde(default = "opaque")]
+    pub opacity: u8,
+    #[serde(default)]
+    pub blend: BlendModeData,
+}
+
+impl PlaneData {
+    pub fn load<'ttf>(&self, resources: &mut dyn Resources<'ttf>) -> Result<Plane<'ttf>, Error> {
+        Ok(Plane::new(self.texture.load(resources)?, self.scroll_factor, self.velocity, self.tiled, self.opacity, self.blend.to_blend_mode()))
+    }
+}
+
+impl Data for PlaneData {
+    fn reroot(&mut self, base_path: &Path) {
+        self.texture.reroot(base_path);
+    }
+}
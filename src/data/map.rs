@@ -1,13 +1,19 @@
+use std::collections::HashMap;
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
 use crate::data::{Data};
-use crate::data::gfx::{TextureData, TilesetData};
+use crate::Error;
+use crate::data::gfx::{ColorData, EmitterData, PlaneData, TextureData, TilesetData};
+use crate::gfx::lighting::{Light, LightAnchor, Lighting};
+use crate::gfx::minimap::{Minimap, PointOfInterest};
+use crate::gfx::weather::{Weather, WeatherKind};
+use crate::point::{FloatPoint, IntPoint};
 use crate::scene::map;
 
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum LayerData {
     Simple{layer: map::Layer<u32>},
     Condensed{rows: Vec<String>},
@@ -28,7 +34,7 @@ impl LayerData {
 }
 
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TilesData {
     Simple{tiles: map::Tiles<u32>},
     ByLayer{layers: Vec<LayerData>},
@@ -43,16 +49,148 @@ impl TilesData {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacedEmitterData {
+    pub emitter: EmitterData,
+    pub position: FloatPoint,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PointOfInterestData {
+    pub tile: IntPoint,
+    pub color: ColorData,
+}
+
+fn default_minimap_scale() -> u32 {
+    4
+}
+
+fn visible() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinimapData {
+    pub position: IntPoint,
+    #[serde(default = "default_minimap_scale")]
+    pub scale: u32,
+    #[serde(default = "visible")]
+    pub visible: bool,
+    // Overrides the colours sampled from the tileset, by tile index
+    #[serde(default)]
+    pub colors: HashMap<u32, ColorData>,
+    #[serde(default)]
+    pub points_of_interest: Vec<PointOfInterestData>,
+}
+
+impl MinimapData {
+    pub fn load<'tx>(&self, tileset: &TilesetData) -> Result<Minimap<'tx>, Error> {
+        let mut colors = tileset.sample_tile_colors().unwrap_or_else(|e| {
+            println!("Could not sample tileset colours for the minimap: {:?}", e);
+            HashMap::new()
+        });
+        colors.extend(self.colors.iter().map(|(tile, color)| (*tile, color.to_color())));
+        let points_of_interest = self.points_of_interest.iter()
+            .map(|point| PointOfInterest { tile: point.tile, color: point.color.to_color() })
+            .collect();
+        Ok(Minimap::new(colors, self.scale, self.position, points_of_interest, self.visible))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LightAnchorData {
+    Character,
+    Tile { x: i32, y: i32 },
+}
+
+// Radius is in pixels, flicker speed in flickers per second
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightData {
+    pub anchor: LightAnchorData,
+    pub color: ColorData,
+    pub radius: f32,
+    #[serde(default)]
+    pub flicker_amplitude: f32,
+    #[serde(default)]
+    pub flicker_speed: f32,
+}
+
+impl LightData {
+    pub fn to_light(&self) -> Light {
+        let anchor = match self.anchor {
+            LightAnchorData::Character => LightAnchor::Character,
+            LightAnchorData::Tile { x, y } => LightAnchor::Tile(IntPoint::new(x, y)),
+        };
+        Light {
+            anchor,
+            color: self.color.to_color(),
+            radius: self.radius,
+            flicker_amplitude: self.flicker_amplitude,
+            flicker_speed: self.flicker_speed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LightingData {
+    pub ambient: ColorData,
+    #[serde(default)]
+    pub lights: Vec<LightData>,
+}
+
+impl LightingData {
+    pub fn to_lighting<'tx>(&self) -> Lighting<'tx> {
+        Lighting::new(self.ambient.to_color(), self.lights.iter().map(|light| light.to_light()).collect())
+    }
+}
+
+fn full_intensity() -> f32 {
+    1.
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeatherData {
+    pub kind: WeatherKind,
+    #[serde(default = "full_intensity")]
+    pub intensity: f32,
+}
+
+impl WeatherData {
+    pub fn to_weather(&self) -> Weather {
+        Weather::new(self.kind, self.intensity)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MapData {
     pub tileset: TilesetData,
     pub character: TextureData,
     pub tiles:  TilesData,
+    #[serde(default)]
+    pub emitters: Vec<PlacedEmitterData>,
+    #[serde(default)]
+    pub minimap: Option<MinimapData>,
+    // Drawn behind the tiles
+    #[serde(default)]
+    pub parallax: Vec<PlaneData>,
+    // Drawn over the tiles
+    #[serde(default)]
+    pub fog: Vec<PlaneData>,
+    #[serde(default)]
+    pub lighting: Option<LightingData>,
+    #[serde(default)]
+    pub weather: Option<WeatherData>,
 }
 
 impl Data for MapData {
     fn reroot(&mut self, base_path: &Path) {
         self.tileset.reroot(base_path);
         self.character.reroot(base_path);
+        for placed in self.emitters.iter_mut() {
+            placed.emitter.reroot(base_path);
+        }
+        for plane in self.parallax.iter_mut().chain(self.fog.iter_mut()) {
+            plane.reroot(base_path);
+        }
     }
 }
\ No newline at end of file
@@ -5,6 +5,7 @@ use std::path::Path;
 use serde::{Deserialize, Serialize};
 use serde::de::DeserializeOwned;
 use crate::data::font::FontData;
+use crate::data::gfx::{CaptureData, WindowSkinData};
 use crate::data::map::MapData;
 
 use crate::error::Error;
@@ -12,17 +13,25 @@ use crate::error::Error;
 pub mod font;
 pub mod gfx;
 pub mod map;
+pub mod settings;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GameConfig {
     pub font: FontData,
     pub map: MapData,
+    #[serde(default)]
+    pub window_skin: Option<WindowSkinData>,
+    #[serde(default)]
+    pub capture: CaptureData,
 }
 
 impl Data for GameConfig {
     fn reroot(&mut self, base_path: &Path) {
         self.font.reroot(base_path);
         self.map.reroot(base_path);
+        if let Some(window_skin) = self.window_skin.as_mut() {
+            window_skin.reroot(base_path);
+        }
     }
 }
 
@@ -0,0 +1,155 @@
+use sdl2::controller::Button;
+use sdl2::keyboard::Scancode;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::gamepad::{DEFAULT_DEADZONE, GamepadMap};
+use crate::hotkeys::{Hotkey, HotkeyMap};
+use crate::keymap::{Action, KeyMap};
+use crate::repeat::RepeatConfig;
+
+// Player settings, saved next to the config. Unlike GameConfig this is written back by the game.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct SettingsData {
+    #[serde(default)]
+    pub key_map: KeyMapData,
+    #[serde(default)]
+    pub gamepad_map: GamepadMapData,
+    #[serde(default)]
+    pub repeat: RepeatConfig,
+    // Bindings of the input contexts, by context name
+    #[serde(default)]
+    pub contexts: Vec<ContextKeyMapData>,
+    #[serde(default)]
+    pub hotkeys: HotkeyMapData,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContextKeyMapData {
+    pub name: String,
+    pub key_map: KeyMapData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct KeyMapData {
+    pub bindings: Vec<KeyBindingData>,
+}
+
+// Keys are stored by their SDL scancode names, e.g. "Up", "W" or "Return"
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeyBindingData {
+    pub action: Action,
+    pub keys: Vec<String>,
+}
+
+impl KeyMapData {
+    pub fn from_key_map(key_map: &KeyMap, actions: &[Action]) -> Self {
+        let bindings = actions.iter()
+            .map(|action| KeyBindingData {
+                action: *action,
+                keys: key_map.get_keys(action).map(|keys| keys.iter().map(|key| key.name().to_owned()).collect()).unwrap_or_default(),
+            })
+            .collect();
+        KeyMapData { bindings }
+    }
+
+    // Actions missing from the file keep the keys they have in the given defaults
+    pub fn to_key_map(&self, defaults: &KeyMap) -> Result<KeyMap, Error> {
+        let mut key_map = defaults.clone();
+        for binding in &self.bindings {
+            key_map.unbind(binding.action);
+            for name in &binding.keys {
+                let key = Scancode::from_name(name).ok_or_else(|| Error::simple(format!("Unknown key {} for {:?}", name, binding.action)))?;
+                key_map.bind(binding.action, key);
+            }
+        }
+        Ok(key_map)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct HotkeyMapData {
+    pub bindings: Vec<HotkeyBindingData>,
+}
+
+// Keys are stored by their SDL scancode names, like KeyBindingData
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HotkeyBindingData {
+    pub hotkey: Hotkey,
+    pub keys: Vec<String>,
+}
+
+impl HotkeyMapData {
+    pub fn from_hotkey_map(hotkey_map: &HotkeyMap) -> Self {
+        let bindings = Hotkey::ALL.iter()
+            .map(|hotkey| HotkeyBindingData {
+                hotkey: *hotkey,
+                keys: hotkey_map.get_keys(*hotkey).iter().map(|key| key.name().to_owned()).collect(),
+            })
+            .collect();
+        HotkeyMapData { bindings }
+    }
+
+    // Hotkeys missing from the file keep their default keys
+    pub fn to_hotkey_map(&self) -> Result<HotkeyMap, Error> {
+        let mut hotkey_map = HotkeyMap::default();
+        for binding in &self.bindings {
+            hotkey_map.unbind(binding.hotkey);
+            for name in &binding.keys {
+                let key = Scancode::from_name(name).ok_or_else(|| Error::simple(format!("Unknown key {} for {:?}", name, binding.hotkey)))?;
+                hotkey_map.bind(binding.hotkey, key);
+            }
+        }
+        Ok(hotkey_map)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GamepadMapData {
+    pub bindings: Vec<ButtonBindingData>,
+    #[serde(default = "default_deadzone")]
+    pub deadzone: i16,
+}
+
+impl Default for GamepadMapData {
+    fn default() -> Self {
+        GamepadMapData { bindings: Vec::new(), deadzone: DEFAULT_DEADZONE }
+    }
+}
+
+fn default_deadzone() -> i16 {
+    DEFAULT_DEADZONE
+}
+
+// Buttons are stored by their SDL mapping names, e.g. "a", "start" or "dpup"
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ButtonBindingData {
+    pub action: Action,
+    pub buttons: Vec<String>,
+}
+
+impl GamepadMapData {
+    pub fn from_gamepad_map(gamepad_map: &GamepadMap) -> Self {
+        let bindings = Action::ALL.iter()
+            .map(|action| ButtonBindingData {
+                action: *action,
+                buttons: gamepad_map.get_buttons(action).map(|buttons| buttons.iter().map(|button| button.string()).collect()).unwrap_or_default(),
+            })
+            .collect();
+        GamepadMapData { bindings, deadzone: gamepad_map.deadzone }
+    }
+
+    // Actions missing from the file keep their default buttons
+    pub fn to_gamepad_map(&self) -> Result<GamepadMap, Error> {
+        let mut gamepad_map = GamepadMap::default();
+        gamepad_map.deadzone = self.deadzone;
+        for binding in &self.bindings {
+            gamepad_map.unbind(binding.action);
+            for name in &binding.buttons {
+                let button = Button::from_string(name).ok_or_else(|| Error::simple(format!("Unknown button {} for {:?}", name, binding.action)))?;
+                gamepad_map.bind(binding.action, button);
+            }
+        }
+        Ok(gamepad_map)
+    }
+}
@@ -1,15 +1,40 @@
 use sdl2::event::Event as SdlEvent;
 use sdl2::EventPump;
-use sdl2::keyboard::{KeyboardState, Keycode};
+use std::path::Path;
+
+use sdl2::keyboard::KeyboardState;
+use sdl2::mouse::MouseButton;
 use sdl2::render::RenderTarget;
-use sdl2::video::WindowContext;
+use serde::{Deserialize, Serialize};
 
-use crate::{CachedResources, Scene};
+use crate::{Error, Resources, Scene};
+use crate::resources::NullResources;
+use crate::game_event::EventBus;
+use crate::gamepad::{GamepadMap, Gamepads};
+use crate::gfx::capture::FrameCapture;
+use crate::hotkeys::HotkeyState;
+use crate::input_context::ActionResolver;
+use crate::input_source::InputSource;
 use crate::keymap::{Action, KeyMap};
+use crate::point::{FloatPoint, IntPoint};
+use crate::repeat::KeyRepeat;
+use crate::replay::{Recorder, Recording, Replay};
+use crate::scene::text_input::TextInputResult;
+use crate::settings::Settings;
 
 pub struct PumpProcessor {
     pump: EventPump,
-    key_map: KeyMap,
+    // None when the game controller subsystem is not available
+    gamepads: Option<Gamepads>,
+    recorder: Option<Recorder>,
+    // While replaying, batches come from the recording and live input is ignored
+    replay: Option<Replay>,
+    key_repeat: KeyRepeat,
+    // View pixels per window point, for when the view is drawn scaled into the window
+    view_scale: FloatPoint,
+    text_input_started: bool,
+    // Input state at the end of the last batch, what Scene::update sees
+    input: InputState,
 }
 
 #[derive(Debug, Clone)]
@@ -17,38 +42,161 @@ pub enum Event {
     Sdl(SdlEvent),
     ActionDown { action: Action },
     ActionUp { action: Action },
+    // Sent while an action is held, see KeyRepeat
+    ActionRepeat { action: Action },
+    // Mouse positions are in view coordinates, the space the scenes are drawn in
+    MouseMove { position: IntPoint },
+    MouseDown { button: MouseButton, position: IntPoint },
+    MouseUp { button: MouseButton, position: IntPoint },
+}
+
+fn dispatch<'ttf, T: RenderTarget, L: EventListener<'ttf, T> + ?Sized>(state: &mut GameState<'ttf>, listener: &mut L, recorder: &mut Option<Recorder>, key_repeat: &mut KeyRepeat, event: &Event) {
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.record(event);
+    }
+    key_repeat.track(event, &state.settings.repeat);
+    listener.process_event(state, event);
+}
+
+fn to_view(view_scale: FloatPoint, x: i32, y: i32) -> IntPoint {
+    FloatPoint::new(x as f32 * view_scale.x, y as f32 * view_scale.y).truncate()
 }
 
 impl PumpProcessor {
-    pub fn new(pump: EventPump, key_map: KeyMap) -> Self {
-        PumpProcessor { pump, key_map }
+    pub fn new(pump: EventPump, gamepads: Option<Gamepads>) -> Self {
+        // SDL starts with text input on. It is kept off unless a scene asks for it, so IMEs do not pop up during play.
+        unsafe { sdl2::sys::SDL_StopTextInput() };
+        PumpProcessor { pump, gamepads, recorder: None, replay: None, key_repeat: KeyRepeat::new(), view_scale: FloatPoint::new(1., 1.), text_input_started: false, input: InputState::default() }
+    }
+
+    pub fn set_view_scale(&mut self, window_size: (u32, u32), view_size: (u32, u32)) {
+        self.view_scale = FloatPoint::new(view_size.0 as f32 / window_size.0.max(1) as f32, view_size.1 as f32 / window_size.1.max(1) as f32);
+    }
+
+    pub fn start_recording<P: AsRef<Path>>(&mut self, path: P) {
+        self.recorder = Some(Recorder::new(path));
     }
 
-    pub fn process_events<'ttf, T: RenderTarget, L: EventListener<'ttf, T>>(&mut self, state: &mut GameState<'ttf>, listener: &mut L) {
-        let start_state = self.get_input_state();
+    // Writes what was recorded so far, does nothing when not recording
+    pub fn stop_recording(&mut self) -> Result<(), Error> {
+        match self.recorder.take() {
+            Some(mut recorder) => recorder.save(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn start_replay(&mut self, recording: Recording) {
+        self.replay = Some(Replay::new(recording));
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub fn input(&self) -> &InputState {
+        &self.input
+    }
+
+    pub fn process_events<'ttf, T: RenderTarget, L: EventListener<'ttf, T> + ?Sized>(&mut self, state: &mut GameState<'ttf>, listener: &mut L) {
+        if self.replay.is_some() {
+            self.replay_batch(state, listener);
+            return;
+        }
+        self.sync_text_input(state);
+
+        let start_state = self.get_input_state(state);
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.begin_batch(state.ticks_to_process, &start_state);
+        }
         listener.batch_start(state, &start_state);
 
         for sdl_event in self.pump.poll_iter() {
+            // Keys without an action are passed through so listeners can still react to them.
+            // Keys are resolved per event so rebinding and context changes take effect right away.
+            if let Some(events) = self.gamepads.as_mut().and_then(|gamepads| gamepads.translate(&sdl_event, &state.settings.gamepad_map)) {
+                for event in events {
+                    dispatch(state, listener, &mut self.recorder, &mut self.key_repeat, &event);
+                }
+                continue;
+            }
+            let resolver = state.settings.resolver(&state.input_contexts);
             let event = match sdl_event {
-                SdlEvent::KeyDown { scancode: Some(scancode), .. } => self.key_map.get_action(&scancode).map(|action| Event::ActionDown { action: *action }),
-                SdlEvent::KeyUp { scancode: Some(scancode), .. } => self.key_map.get_action(&scancode).map(|action| Event::ActionUp { action: *action }),
-                _ => Some(Event::Sdl(sdl_event)),
-            };
-            event.and_then(|e| listener.process_event(state, &e));
+                _ if state.raw_keys => None,
+                // OS key repeat is replaced by KeyRepeat, which works the same for every device
+                SdlEvent::KeyDown { scancode: Some(scancode), repeat: true, .. } if resolver.get_action(&scancode).is_some() => continue,
+                SdlEvent::KeyDown { scancode: Some(scancode), .. } => resolver.get_action(&scancode).map(|action| Event::ActionDown { action }),
+                SdlEvent::KeyUp { scancode: Some(scancode), .. } => resolver.get_action(&scancode).map(|action| Event::ActionUp { action }),
+                SdlEvent::MouseMotion { x, y, .. } => Some(Event::MouseMove { position: to_view(self.view_scale, x, y) }),
+                SdlEvent::MouseButtonDown { mouse_btn, x, y, .. } => Some(Event::MouseDown { button: mouse_btn, position: to_view(self.view_scale, x, y) }),
+                SdlEvent::MouseButtonUp { mouse_btn, x, y, .. } => Some(Event::MouseUp { button: mouse_btn, position: to_view(self.view_scale, x, y) }),
+                _ => None,
+            }.unwrap_or(Event::Sdl(sdl_event));
+            dispatch(state, listener, &mut self.recorder, &mut self.key_repeat, &event);
         }
 
-        let end_state = self.get_input_state();
+        let end_state = self.get_input_state(state);
+        for event in self.key_repeat.advance(state.ticks_to_process, &end_state, &state.settings.repeat) {
+            dispatch(state, listener, &mut self.recorder, &mut self.key_repeat, &event);
+        }
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.end_batch(&end_state);
+        }
         listener.batch_end(state, &end_state);
+        self.input = end_state;
     }
 
-    fn get_input_state(&self) -> InputState {
-        InputState::new(SdlInputState::from(&self.pump), &self.key_map)
+    fn sync_text_input(&mut self, state: &GameState) {
+        if state.text_input != self.text_input_started {
+            unsafe {
+                if state.text_input { sdl2::sys::SDL_StartTextInput() } else { sdl2::sys::SDL_StopTextInput() }
+            }
+            self.text_input_started = state.text_input;
+        }
+    }
+
+    fn replay_batch<'ttf, T: RenderTarget, L: EventListener<'ttf, T> + ?Sized>(&mut self, state: &mut GameState<'ttf>, listener: &mut L) {
+        // Live input is dropped so it can not change the outcome, except for closing the window
+        for sdl_event in self.pump.poll_iter() {
+            if let SdlEvent::Quit { .. } = sdl_event {
+                listener.process_event(state, &Event::Sdl(sdl_event));
+            }
+        }
+
+        match self.replay.as_mut() {
+            Some(replay) if !replay.is_finished() => {
+                replay.process_events(state, listener);
+                self.input = replay.input().clone();
+            }
+            _ => {
+                println!("Replay finished");
+                self.replay = None;
+            }
+        }
+    }
+
+    fn get_input_state(&self, state: &GameState) -> InputState {
+        InputState::capture(&SdlInputState::from(&self.pump), self.gamepads.as_ref(), &state.settings.resolver(&state.input_contexts), &state.settings.gamepad_map)
     }
 }
 
 pub enum EventResult<'ttf, T: RenderTarget> {
     PushScene(Box<dyn Scene<'ttf, T> + 'ttf>),
+    // Pushes a scene drawn over the current one, e.g. a pause menu. With update_below the scenes
+    // beneath keep animating, they never get events.
+    PushOverlay { scene: Box<dyn Scene<'ttf, T> + 'ttf>, update_below: bool },
     PopScene,
+    // Pops the current scene and pushes the given one in its place
+    ReplaceScene(Box<dyn Scene<'ttf, T> + 'ttf>),
+    // Pops every scene above the topmost one with the given Scene::name
+    PopTo(&'static str),
+    ClearAndPush(Box<dyn Scene<'ttf, T> + 'ttf>),
+    Quit,
+    // Returned by a global listener to keep an event from the scenes, without any transition
+    Consumed,
 }
 
 pub struct SdlInputState<'r> {
@@ -63,20 +211,34 @@ impl<'r> SdlInputState<'r> {
     }
 }
 
-pub struct InputState<'r> {
-    sdl_state: SdlInputState<'r>,
-    key_map: &'r KeyMap,
+// Actions held down at one point in time. It is a plain snapshot so it can be recorded and replayed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    pressed: Vec<Action>,
 }
 
-impl<'r> InputState<'r> {
-    pub fn new(sdl_state: SdlInputState<'r>, key_map: &'r KeyMap) -> Self {
-        InputState { sdl_state, key_map }
+impl InputState {
+    pub fn pressed(&self) -> &[Action] {
+        &self.pressed
+    }
+
+    pub fn new(pressed: Vec<Action>) -> Self {
+        InputState { pressed }
+    }
+
+    pub fn capture(sdl_state: &SdlInputState, gamepads: Option<&Gamepads>, resolver: &ActionResolver, gamepad_map: &GamepadMap) -> Self {
+        let pressed = Action::ALL.iter()
+            .copied()
+            .filter(|action| {
+                resolver.get_keys(*action).iter().any(|key| sdl_state.keyboard.is_scancode_pressed(*key))
+                    || gamepads.map(|gamepads| gamepads.is_action_pressed(*action, gamepad_map)).unwrap_or(false)
+            })
+            .collect();
+        InputState { pressed }
     }
 
     pub fn is_action_pressed(&self, action: Action) -> bool {
-        self.key_map.get_keys(&action)
-            .map(|keys| keys.iter().any(|key| self.sdl_state.keyboard.is_scancode_pressed(*key)))
-            .unwrap_or(false)
+        self.pressed.contains(&action)
     }
 }
 
@@ -84,15 +246,47 @@ impl<'r> InputState<'r> {
 pub struct GameState<'tx> {
     pub running: bool,
     pub ticks_to_process: u32,
-    pub resources: CachedResources<'tx, WindowContext>,
+    pub resources: Box<dyn Resources<'tx> + 'tx>,
+    pub capture: FrameCapture,
+    pub settings: Settings,
+    // When set, key events are delivered as raw SDL events instead of actions, e.g. while waiting for a key to bind
+    pub raw_keys: bool,
+    // Whether SDL should deliver text input events, set by scenes that take typed text
+    pub text_input: bool,
+    pub text_input_result: Option<TextInputResult>,
+    // Names of the input contexts of the scenes at the top of the stack, bottom first
+    pub input_contexts: Vec<String>,
+    pub hotkeys: HotkeyState,
+    pub events: EventBus<'tx>,
 }
 
 impl<'tx> GameState<'tx> {
-    pub fn new(resources: CachedResources<'tx, WindowContext>) -> Self {
+    pub fn new(resources: Box<dyn Resources<'tx> + 'tx>, capture: FrameCapture, settings: Settings) -> Self {
         GameState {
             running: true,
             ticks_to_process: 0,
             resources,
+            capture,
+            settings,
+            raw_keys: false,
+            text_input: false,
+            text_input_result: None,
+            input_contexts: Vec::new(),
+            hotkeys: HotkeyState::default(),
+            events: EventBus::new(),
+        }
+    }
+
+    // State without SDL resources or a settings file, for driving scenes headless
+    pub fn headless() -> Self {
+        GameState::new(Box::new(NullResources {}), FrameCapture::new("target/capture", 1), Settings::new(KeyMap::default(), GamepadMap::default(), None))
+    }
+
+    // Returns the text entered for the given id, if a TextInputScene just finished with it
+    pub fn take_text_input(&mut self, id: &str) -> Option<String> {
+        match &self.text_input_result {
+            Some(result) if result.id == id => self.text_input_result.take().map(|result| result.text),
+            _ => None,
         }
     }
 }
@@ -102,21 +296,3 @@ pub trait EventListener<'ttf, T: RenderTarget> {
     fn process_event(&mut self, _state: &mut GameState<'ttf>, _event: &Event) -> Option<EventResult<'ttf, T>> { None }
     fn batch_end(&mut self, _state: &mut GameState<'ttf>, _input: &InputState) -> Option<EventResult<'ttf, T>> { None }
 }
-
-pub struct QuitListener {}
-
-impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for QuitListener {
-    fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
-        match event {
-            Event::Sdl(SdlEvent::Quit { .. }
-                       | SdlEvent::KeyDown {
-                keycode: Some(Keycode::Q),
-                ..
-            }) => {
-                state.running = false;
-                None
-            }
-            _ => None
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+
+use crate::event::GameState;
+use crate::gfx::weather::WeatherKind;
+use crate::point::IntPoint;
+
+// Dispatching stops after this many events in one frame, in case subscribers keep publishing in a loop
+const MAX_EVENTS_PER_DISPATCH: usize = 1000;
+
+// Things that happened in the game, for systems that should not know about the scene that caused them
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    HeroNamed { name: String },
+    MapEntered,
+    MapLeft,
+    // The hero used Interact while standing on the tile
+    Interacted { tile: IntPoint },
+    // Asks the map on screen to change its weather
+    WeatherChanged { kind: WeatherKind, intensity: f32 },
+}
+
+pub trait GameEventSubscriber<'tx> {
+    fn notify(&mut self, state: &mut GameState<'tx>, event: &GameEvent);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriberId(u32);
+
+// Queue of game events on GameState. Published events are delivered once per frame by dispatch,
+// in the order they were published, each to the subscribers in the order they subscribed.
+// Events published by subscribers are delivered in the same dispatch, after the ones already queued.
+#[derive(Default)]
+pub struct EventBus<'tx> {
+    queue: VecDeque<GameEvent>,
+    subscribers: Vec<(SubscriberId, Box<dyn GameEventSubscriber<'tx> + 'tx>)>,
+    // Unsubscribed while dispatching, removed once it is over
+    unsubscribed: Vec<SubscriberId>,
+    next_id: u32,
+}
+
+impl<'tx> EventBus<'tx> {
+    pub fn new() -> Self {
+        EventBus { queue: VecDeque::new(), subscribers: Vec::new(), unsubscribed: Vec::new(), next_id: 0 }
+    }
+
+    pub fn publish(&mut self, event: GameEvent) {
+        self.queue.push_back(event);
+    }
+
+    // Subscribers added while dispatching get events from the next dispatch on
+    pub fn subscribe(&mut self, subscriber: Box<dyn GameEventSubscriber<'tx> + 'tx>) -> SubscriberId {
+        let id = SubscriberId(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push((id, subscriber));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: SubscriberId) {
+        self.subscribers.retain(|(subscriber, _)| *subscriber != id);
+        self.unsubscribed.push(id);
+    }
+
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+
+    // Delivers every queued event. Subscribers are taken out of the bus meanwhile, so they can get the whole game state.
+    pub fn dispatch(state: &mut GameState<'tx>) {
+        let mut subscribers = std::mem::take(&mut state.events.subscribers);
+        let mut delivered = 0;
+        while let Some(event) = state.events.queue.pop_front() {
+            if delivered == MAX_EVENTS_PER_DISPATCH {
+                println!("Dropping {} game events, too many were published in one frame", state.events.queue.len() + 1);
+                state.events.queue.clear();
+                break;
+            }
+            delivered += 1;
+            for (id, subscriber) in subscribers.iter_mut() {
+                if !state.events.unsubscribed.contains(id) {
+                    subscriber.notify(state, &event);
+                }
+            }
+        }
+        let unsubscribed = std::mem::take(&mut state.events.unsubscribed);
+        subscribers.retain(|(id, _)| !unsubscribed.contains(id));
+        subscribers.append(&mut state.events.subscribers);
+        state.events.subscribers = subscribers;
+    }
+}
+
+// Prints every game event, handy to follow what the systems see
+pub struct EventLog {}
+
+impl<'tx> GameEventSubscriber<'tx> for EventLog {
+    fn notify(&mut self, _state: &mut GameState<'tx>, event: &GameEvent) {
+        println!("Game event: {:?}", event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    type Log = Rc<RefCell<Vec<(&'static str, GameEvent)>>>;
+
+    // Logs what it gets, publishes MapLeft after MapEntered and keeps republishing Interacted when looping
+    struct Recorder {
+        name: &'static str,
+        log: Log,
+        looping: bool,
+    }
+
+    impl<'tx> GameEventSubscriber<'tx> for Recorder {
+        fn notify(&mut self, state: &mut GameState<'tx>, event: &GameEvent) {
+            self.log.borrow_mut().push((self.name, event.clone()));
+            match event {
+                GameEvent::MapEntered => state.events.publish(GameEvent::MapLeft),
+                GameEvent::Interacted { tile } if self.looping => state.events.publish(GameEvent::Interacted { tile: *tile + IntPoint::new(1, 0) }),
+                _ => {}
+            }
+        }
+    }
+
+    // Unsubscribes itself and the given subscriber on its first event, and subscribes a newcomer
+    struct Leaver {
+        id: Rc<RefCell<Option<SubscriberId>>>,
+        other: SubscriberId,
+        log: Log,
+    }
+
+    impl<'tx> GameEventSubscriber<'tx> for Leaver {
+        fn notify(&mut self, state: &mut GameState<'tx>, _event: &GameEvent) {
+            if let Some(id) = self.id.borrow_mut().take() {
+                state.events.unsubscribe(id);
+                state.events.unsubscribe(self.other);
+                state.events.subscribe(Box::new(Recorder { name: "newcomer", log: self.log.clone(), looping: false }));
+            }
+        }
+    }
+
+    fn recorder(state: &mut GameState, name: &'static str, log: &Log, looping: bool) -> SubscriberId {
+        state.events.subscribe(Box::new(Recorder { name, log: log.clone(), looping }))
+    }
+
+    #[test]
+    fn dispatch_order() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        recorder(&mut state, "first", &log, false);
+        recorder(&mut state, "second", &log, false);
+        state.events.publish(GameEvent::MapEntered);
+        state.events.publish(GameEvent::HeroNamed { name: "Ann".to_owned() });
+        EventBus::dispatch(&mut state);
+
+        // Published order, then subscription order, with events published meanwhile after the queued ones
+        let named = GameEvent::HeroNamed { name: "Ann".to_owned() };
+        assert_eq!(*log.borrow(), vec![
+            ("first", GameEvent::MapEntered),
+            ("second", GameEvent::MapEntered),
+            ("first", named.clone()),
+            ("second", named),
+            ("first", GameEvent::MapLeft),
+            ("second", GameEvent::MapLeft),
+            ("first", GameEvent::MapLeft),
+            ("second", GameEvent::MapLeft),
+        ]);
+        assert_eq!(state.events.pending(), 0);
+    }
+
+    #[test]
+    fn subscribing_while_dispatching() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        let leaver_id = Rc::new(RefCell::new(None));
+        let before = recorder(&mut state, "before", &log, false);
+        let id = state.events.subscribe(Box::new(Leaver { id: leaver_id.clone(), other: before, log: log.clone() }));
+        *leaver_id.borrow_mut() = Some(id);
+        recorder(&mut state, "after", &log, false);
+
+        state.events.publish(GameEvent::MapLeft);
+        state.events.publish(GameEvent::MapLeft);
+        EventBus::dispatch(&mut state);
+        // Unsubscribed ones stop right away, new ones wait for the next dispatch
+        assert_eq!(*log.borrow(), vec![("before", GameEvent::MapLeft), ("after", GameEvent::MapLeft), ("after", GameEvent::MapLeft)]);
+
+        log.borrow_mut().clear();
+        state.events.publish(GameEvent::MapLeft);
+        EventBus::dispatch(&mut state);
+        assert_eq!(*log.borrow(), vec![("after", GameEvent::MapLeft), ("newcomer", GameEvent::MapLeft)]);
+    }
+
+    #[test]
+    fn publishing_loops_are_capped() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        recorder(&mut state, "looping", &log, true);
+        state.events.publish(GameEvent::Interacted { tile: IntPoint::new(0, 0) });
+        state.events.publish(GameEvent::MapLeft);
+        EventBus::dispatch(&mut state);
+
+        assert_eq!(log.borrow().len(), MAX_EVENTS_PER_DISPATCH);
+        assert_eq!(log.borrow()[1].1, GameEvent::MapLeft);
+        assert_eq!(log.borrow()[MAX_EVENTS_PER_DISPATCH - 1].1, GameEvent::Interacted { tile: IntPoint::new(MAX_EVENTS_PER_DISPATCH as i32 - 2, 0) });
+        // The rest is dropped, the next frame starts clean
+        assert_eq!(state.events.pending(), 0);
+        log.borrow_mut().clear();
+        state.events.publish(GameEvent::MapLeft);
+        EventBus::dispatch(&mut state);
+        assert_eq!(*log.borrow(), vec![("looping", GameEvent::MapLeft)]);
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::{HashMap, HashSet};
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event as SdlEvent;
+use sdl2::GameControllerSubsystem;
+
+use crate::error::Error;
+use crate::event::Event;
+use crate::keymap::Action;
+
+// Stick values go from -32768 to 32767, anything closer to the center than this is ignored
+pub const DEFAULT_DEADZONE: i16 = 8000;
+
+#[derive(Debug, Clone)]
+pub struct GamepadMap {
+    button_to_action: HashMap<Button, Action>,
+    action_to_buttons: HashMap<Action, Vec<Button>>,
+    pub deadzone: i16,
+}
+
+impl GamepadMap {
+    pub fn new(deadzone: i16) -> Self {
+        GamepadMap { button_to_action: HashMap::new(), action_to_buttons: HashMap::new(), deadzone }
+    }
+
+    pub fn get_action(&self, button: &Button) -> Option<&Action> {
+        self.button_to_action.get(button)
+    }
+
+    pub fn get_buttons(&self, action: &Action) -> Option<&Vec<Button>> {
+        self.action_to_buttons.get(action)
+    }
+
+    // Binds the button to the action, taking it away from any other action
+    pub fn bind(&mut self, action: Action, button: Button) {
+        if let Some(previous) = self.button_to_action.insert(button, action) {
+            if let Some(buttons) = self.action_to_buttons.get_mut(&previous) {
+                buttons.retain(|b| *b != button);
+            }
+        }
+        self.action_to_buttons.entry(action).or_default().push(button);
+    }
+
+    pub fn unbind(&mut self, action: Action) {
+        if let Some(buttons) = self.action_to_buttons.remove(&action) {
+            for button in buttons {
+                self.button_to_action.remove(&button);
+            }
+        }
+    }
+
+    // The left stick always moves, only its deadzone is configurable
+    fn stick_action(&self, axis: Axis, value: i16) -> Option<Action> {
+        let value = value as i32;
+        let deadzone = self.deadzone as i32;
+        match axis {
+            Axis::LeftX if value < -deadzone => Some(Action::Left),
+            Axis::LeftX if value > deadzone => Some(Action::Right),
+            Axis::LeftY if value < -deadzone => Some(Action::Up),
+            Axis::LeftY if value > deadzone => Some(Action::Down),
+            _ => None,
+        }
+    }
+}
+
+impl Default for GamepadMap {
+    fn default() -> Self {
+        hardcoded_gamepad_map()
+    }
+}
+
+pub fn hardcoded_gamepad_map() -> GamepadMap {
+    let button_map = [
+        (Button::DPadUp, Action::Up),
+        (Button::DPadDown, Action::Down),
+        (Button::DPadLeft, Action::Left),
+        (Button::DPadRight, Action::Right),
+        (Button::A, Action::Select),
+        (Button::Start, Action::Select),
+        (Button::B, Action::Back),
+    ];
+
+    let mut gamepad_map = GamepadMap::new(DEFAULT_DEADZONE);
+    for (button, action) in button_map.iter() {
+        gamepad_map.bind(*action, *button);
+    }
+    gamepad_map
+}
+
+struct Gamepad {
+    controller: GameController,
+    pressed: HashSet<Button>,
+    // Direction each stick axis currently points to, so crossing the deadzone sends a single down and up
+    stick_x: Option<Action>,
+    stick_y: Option<Action>,
+}
+
+// Open game controllers, keyed by joystick instance id. Controllers are opened and closed
+// as SDL reports them, which includes the ones already plugged in at startup.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    gamepads: HashMap<u32, Gamepad>,
+}
+
+impl Gamepads {
+    pub fn new(subsystem: GameControllerSubsystem) -> Self {
+        Gamepads { subsystem, gamepads: HashMap::new() }
+    }
+
+    pub fn count(&self) -> usize {
+        self.gamepads.len()
+    }
+
+    // Translates controller events into action events. Returns None for events that are not about controllers.
+    pub fn translate(&mut self, event: &SdlEvent, map: &GamepadMap) -> Option<Vec<Event>> {
+        match event {
+            SdlEvent::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.open(*which) {
+                    Ok(controller) => {
+                        println!("Gamepad connected: {}", controller.name());
+                        self.gamepads.insert(controller.instance_id(), Gamepad { controller, pressed: HashSet::new(), stick_x: None, stick_y: None });
+                    }
+                    Err(e) => println!("Could not open gamepad {}: {}", which, e),
+                }
+                Some(Vec::new())
+            }
+            SdlEvent::ControllerDeviceRemoved { which, .. } => {
+                // Release whatever the controller was holding, so nothing stays pressed after unplugging
+                let released = self.gamepads.remove(which)
+                    .map(|gamepad| {
+                        println!("Gamepad disconnected: {}", gamepad.controller.name());
+                        let mut actions: Vec<Action> = gamepad.pressed.iter()
+                            .filter_map(|button| map.get_action(button).copied())
+                            .chain(gamepad.stick_x)
+                            .chain(gamepad.stick_y)
+                            .collect();
+                        actions.sort_by_key(|action| Action::ALL.iter().position(|a| a == action));
+                        actions.dedup();
+                        actions.into_iter().map(|action| Event::ActionUp { action }).collect()
+                    })
+                    .unwrap_or_default();
+                Some(released)
+            }
+            SdlEvent::ControllerButtonDown { which, button, .. } => {
+                if let Some(gamepad) = self.gamepads.get_mut(which) {
+                    gamepad.pressed.insert(*button);
+                }
+                Some(map.get_action(button).map(|action| Event::ActionDown { action: *action }).into_iter().collect())
+            }
+            SdlEvent::ControllerButtonUp { which, button, .. } => {
+                if let Some(gamepad) = self.gamepads.get_mut(which) {
+                    gamepad.pressed.remove(button);
+                }
+                Some(map.get_action(button).map(|action| Event::ActionUp { action: *action }).into_iter().collect())
+            }
+            SdlEvent::ControllerAxisMotion { which, axis, value, .. } => {
+                let direction = map.stick_action(*axis, *value);
+                let current = match (self.gamepads.get_mut(which), axis) {
+                    (Some(gamepad), Axis::LeftX) => &mut gamepad.stick_x,
+                    (Some(gamepad), Axis::LeftY) => &mut gamepad.stick_y,
+                    _ => return Some(Vec::new()),
+                };
+                if *current == direction {
+                    return Some(Vec::new());
+                }
+                let events = current.map(|action| Event::ActionUp { action }).into_iter()
+                    .chain(direction.map(|action| Event::ActionDown { action }))
+                    .collect();
+                *current = direction;
+                Some(events)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_action_pressed(&self, action: Action, map: &GamepadMap) -> bool {
+        self.gamepads.values().any(|gamepad| {
+            gamepad.stick_x == Some(action) || gamepad.stick_y == Some(action)
+                || gamepad.pressed.iter().any(|button| map.get_action(button) == Some(&action))
+        })
+    }
+}
+
+// Controller that only exists in software, for driving the input code without hardware.
+// Buttons and axes are numbered like sdl2::controller::Button and Axis. Values set here
+// show up as controller events on the next pump.
+pub struct VirtualGamepad {
+    device_index: i32,
+    joystick: *mut sdl2::sys::SDL_Joystick,
+}
+
+impl VirtualGamepad {
+    pub fn attach() -> Result<Self, Error> {
+        let button_count = Button::Touchpad as i32 + 1;
+        let axis_count = Axis::TriggerRight as i32 + 1;
+        let device_index = unsafe {
+            sdl2::sys::SDL_JoystickAttachVirtual(sdl2::sys::SDL_JoystickType::SDL_JOYSTICK_TYPE_GAMECONTROLLER, axis_count, button_count, 0)
+        };
+        if device_index < 0 {
+            return Err(Error::simple(format!("Could not attach virtual gamepad: {}", sdl2::get_error())));
+        }
+        let joystick = unsafe { sdl2::sys::SDL_JoystickOpen(device_index) };
+        if joystick.is_null() {
+            unsafe { sdl2::sys::SDL_JoystickDetachVirtual(device_index) };
+            return Err(Error::simple(format!("Could not open virtual gamepad: {}", sdl2::get_error())));
+        }
+        Ok(VirtualGamepad { device_index, joystick })
+    }
+
+    pub fn set_button(&mut self, button: Button, pressed: bool) -> Result<(), Error> {
+        let result = unsafe { sdl2::sys::SDL_JoystickSetVirtualButton(self.joystick, button as i32, pressed as u8) };
+        if result < 0 {
+            return Err(Error::simple(sdl2::get_error()));
+        }
+        Ok(())
+    }
+
+    pub fn set_axis(&mut self, axis: Axis, value: i16) -> Result<(), Error> {
+        let result = unsafe { sdl2::sys::SDL_JoystickSetVirtualAxis(self.joystick, axis as i32, value) };
+        if result < 0 {
+            return Err(Error::simple(sdl2::get_error()));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for VirtualGamepad {
+    fn drop(&mut self) {
+        unsafe {
+            sdl2::sys::SDL_JoystickClose(self.joystick);
+            sdl2::sys::SDL_JoystickDetachVirtual(self.device_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::EventPump;
+
+    use super::*;
+
+    #[test]
+    fn stick_deadzone() {
+        let map = GamepadMap::new(8000);
+        assert_eq!(map.stick_action(Axis::LeftX, 8000), None);
+        assert_eq!(map.stick_action(Axis::LeftX, -8000), None);
+        assert_eq!(map.stick_action(Axis::LeftX, 8001), Some(Action::Right));
+        assert_eq!(map.stick_action(Axis::LeftY, i16::MIN), Some(Action::Up));
+        assert_eq!(map.stick_action(Axis::LeftY, i16::MAX), Some(Action::Down));
+        assert_eq!(map.stick_action(Axis::RightX, i16::MAX), None);
+    }
+
+    // Translates what SDL queued, as (down, action) pairs
+    fn pump(events: &mut EventPump, gamepads: &mut Gamepads, map: &GamepadMap) -> Vec<(bool, Action)> {
+        events.poll_iter()
+            .filter_map(|event| gamepads.translate(&event, map))
+            .flatten()
+            .filter_map(|event| match event {
+                Event::ActionDown { action } => Some((true, action)),
+                Event::ActionUp { action } => Some((false, action)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Needs SDL 2.0.14 or later for virtual joysticks
+    #[test]
+    fn virtual_gamepad() {
+        let sdl2 = crate::test_sdl::init();
+        let mut events = sdl2.sdl.event_pump().unwrap();
+        let mut gamepads = Gamepads::new(sdl2.sdl.game_controller().unwrap());
+        let map = GamepadMap::default();
+
+        let mut pad = VirtualGamepad::attach().unwrap();
+        assert!(pump(&mut events, &mut gamepads, &map).is_empty());
+        assert_eq!(gamepads.count(), 1);
+
+        pad.set_button(Button::A, true).unwrap();
+        assert_eq!(pump(&mut events, &mut gamepads, &map), vec![(true, Action::Select)]);
+        assert!(gamepads.is_action_pressed(Action::Select, &map));
+        pad.set_button(Button::Back, true).unwrap();
+        assert!(pump(&mut events, &mut gamepads, &map).is_empty());
+
+        pad.set_axis(Axis::LeftX, DEFAULT_DEADZONE).unwrap();
+        assert!(pump(&mut events, &mut gamepads, &map).is_empty());
+        pad.set_axis(Axis::LeftX, i16::MAX).unwrap();
+        assert_eq!(pump(&mut events, &mut gamepads, &map), vec![(true, Action::Right)]);
+        pad.set_axis(Axis::LeftX, i16::MIN).unwrap();
+        assert_eq!(pump(&mut events, &mut gamepads, &map), vec![(false, Action::Right), (true, Action::Left)]);
+        assert!(gamepads.is_action_pressed(Action::Left, &map));
+
+        // Unplugging releases everything once, whether SDL recenters the pad first or not
+        drop(pad);
+        let mut released = pump(&mut events, &mut gamepads, &map);
+        released.sort_by_key(|(_, action)| Action::ALL.iter().position(|a| a == action));
+        assert_eq!(released, vec![(false, Action::Left), (false, Action::Select)]);
+        assert_eq!(gamepads.count(), 0);
+        assert!(!gamepads.is_action_pressed(Action::Select, &map));
+    }
+}
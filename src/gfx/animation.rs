@@ -46,7 +46,7 @@ impl<'sdl, T: RenderTarget> Animation<T> for BasicCharAnimation<'sdl> {
 
         let texture_rect = self.sheet.get_sprite(sprite_x, sprite_y)?;
         let dest_rect = Rect::new(dest.x - texture_rect.width() as i32 / 2, dest.y - texture_rect.height() as i32, texture_rect.width(), texture_rect.height());
-        Ok(renderer.copy(texture_rect.texture(), texture_rect.rect(), dest_rect)?)
+        renderer.copy_texture_rect(&texture_rect, dest_rect)
     }
 }
 
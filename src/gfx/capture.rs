@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sdl2::image::SaveSurface;
+use sdl2::surface::Surface;
+
+use crate::Error;
+use crate::gfx::renderer::BackBuffer;
+
+struct FrameSequence {
+    directory: PathBuf,
+    frame_count: u32,
+    saved_count: u32,
+}
+
+// Saves screenshots and image sequences of the back buffer. Requests are queued while handling
+// events and served once the frame has been rendered
+pub struct FrameCapture {
+    directory: PathBuf,
+    sequence_interval: u32,
+    screenshot_requested: bool,
+    sequence: Option<FrameSequence>,
+}
+
+fn timestamp() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0)
+}
+
+pub fn save_png<P: AsRef<Path>>(surface: &Surface, path: P) -> Result<(), Error> {
+    if let Some(parent) = path.as_ref().parent() {
+        fs::create_dir_all(parent)?;
+    }
+    surface.save(&path).map_err(|e| Error::simple(e).with_path(&path))
+}
+
+impl FrameCapture {
+    pub fn new<P: AsRef<Path>>(directory: P, sequence_interval: u32) -> Self {
+        FrameCapture {
+            directory: directory.as_ref().to_owned(),
+            sequence_interval: sequence_interval.max(1),
+            screenshot_requested: false,
+            sequence: None,
+        }
+    }
+
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    pub fn start_sequence(&mut self) {
+        let directory = self.directory.join(format!("sequence-{}", timestamp()));
+        println!("Recording every {} frames to {:?}", self.sequence_interval, directory);
+        self.sequence = Some(FrameSequence { directory, frame_count: 0, saved_count: 0 });
+    }
+
+    pub fn stop_sequence(&mut self) {
+        if let Some(sequence) = self.sequence.take() {
+            println!("Saved {} frames to {:?}", sequence.saved_count, sequence.directory);
+        }
+    }
+
+    pub fn toggle_sequence(&mut self) {
+        if self.is_recording() {
+            self.stop_sequence();
+        } else {
+            self.start_sequence();
+        }
+    }
+
+    // Call after every rendered frame. A capture that fails is not tried again: the screenshot
+    // request is dropped and the sequence stopped, so the error does not come back every frame.
+    pub fn capture_frame(&mut self, back_buffer: &mut BackBuffer) -> Result<(), Error> {
+        let result = self.try_capture_frame(back_buffer);
+        if result.is_err() {
+            self.screenshot_requested = false;
+            self.stop_sequence();
+        }
+        result
+    }
+
+    fn try_capture_frame(&mut self, back_buffer: &mut BackBuffer) -> Result<(), Error> {
+        let save_sequence_frame = match &self.sequence {
+            Some(sequence) => sequence.frame_count % self.sequence_interval == 0,
+            None => false,
+        };
+        if !self.screenshot_requested && !save_sequence_frame {
+            if let Some(sequence) = self.sequence.as_mut() {
+                sequence.frame_count += 1;
+            }
+            return Ok(());
+        }
+
+        let surface = back_buffer.read_surface()?;
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            let path = self.directory.join(format!("screenshot-{}.png", timestamp()));
+            save_png(&surface, &path)?;
+            println!("Saved screenshot to {:?}", path);
+        }
+        if let Some(sequence) = self.sequence.as_mut() {
+            if save_sequence_frame {
+                save_png(&surface, sequence.directory.join(format!("frame-{:06}.png", sequence.saved_count)))?;
+                sequence.saved_count += 1;
+            }
+            sequence.frame_count += 1;
+        }
+        Ok(())
+    }
+}
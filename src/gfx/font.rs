@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::RenderTarget;
+use sdl2::ttf::Font;
+
+use crate::{Error, Resources};
+use crate::gfx::renderer::Renderer;
+use crate::gfx::texture::Texture;
+use crate::point::IntPoint;
+
+// Shared interface for TTF and bitmap fonts, so text can be drawn the same way with either
+pub trait TextFont<'ttf> {
+    fn size_of(&self, text: &str) -> Result<(u32, u32), Error>;
+    fn line_height(&self) -> u32;
+    fn render(&self, text: &str, color: Color, resources: &mut dyn Resources<'ttf>) -> Result<RenderedText<'ttf>, Error>;
+}
+
+struct TextQuad<'ttf> {
+    texture: Rc<Texture<'ttf>>,
+    src: Rect,
+    dst: Rect,
+}
+
+pub struct RenderedText<'ttf> {
+    quads: Vec<TextQuad<'ttf>>,
+    tint: Color,
+    width: u32,
+    height: u32,
+}
+
+impl<'ttf> RenderedText<'ttf> {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn draw<T: RenderTarget>(&self, renderer: &mut Renderer<T>, position: IntPoint) -> Result<(), Error> {
+        for quad in &self.quads {
+            let dst = Rect::new(quad.dst.x() + position.x, quad.dst.y() + position.y, quad.dst.width(), quad.dst.height());
+            renderer.copy_tinted(&quad.texture, quad.src, dst, self.tint)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'ttf> TextFont<'ttf> for Font<'ttf, 'static> {
+    fn size_of(&self, text: &str) -> Result<(u32, u32), Error> {
+        Ok(Font::size_of(self, text)?)
+    }
+
+    fn line_height(&self) -> u32 {
+        self.recommended_line_spacing().max(0) as u32
+    }
+
+    fn render(&self, text: &str, color: Color, resources: &mut dyn Resources<'ttf>) -> Result<RenderedText<'ttf>, Error> {
+        let surface = Font::render(self, text).blended(color)?;
+        let texture = resources.texture_from_surface(surface)?;
+        let (width, height) = (texture.width(), texture.height());
+        Ok(RenderedText {
+            quads: vec![TextQuad { texture: Rc::new(texture), src: Rect::new(0, 0, width, height), dst: Rect::new(0, 0, width, height) }],
+            tint: Color::WHITE,
+            width,
+            height,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub page: usize,
+    // None for blank glyphs such as spaces
+    pub rect: Option<Rect>,
+    pub offset: IntPoint,
+    pub advance: i32,
+}
+
+pub struct BitmapFont<'tx> {
+    pages: Vec<Rc<Texture<'tx>>>,
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), i32>,
+    line_height: u32,
+}
+
+impl<'tx> BitmapFont<'tx> {
+    pub fn new(pages: Vec<Rc<Texture<'tx>>>, glyphs: HashMap<char, Glyph>, kerning: HashMap<(char, char), i32>, line_height: u32) -> Self {
+        BitmapFont { pages, glyphs, kerning, line_height }
+    }
+
+    // Calls placer with each visible glyph and its pen position, returns the size of the whole text
+    fn layout<F: FnMut(&Glyph, IntPoint)>(&self, text: &str, mut placer: F) -> (u32, u32) {
+        let mut pen = IntPoint::new(0, 0);
+        let mut width = 0;
+        let mut previous: Option<char> = None;
+        for character in text.chars() {
+            if character == '\n' {
+                pen = IntPoint::new(0, pen.y + self.line_height as i32);
+                previous = None;
+                continue;
+            }
+            if let Some(glyph) = self.glyphs.get(&character) {
+                if let Some(amount) = previous.and_then(|p| self.kerning.get(&(p, character))) {
+                    pen.x += amount;
+                }
+                placer(glyph, pen);
+                pen.x += glyph.advance;
+                width = width.max(pen.x);
+            }
+            previous = Some(character);
+        }
+        (width.max(0) as u32, pen.y as u32 + self.line_height)
+    }
+}
+
+impl<'tx> TextFont<'tx> for BitmapFont<'tx> {
+    fn size_of(&self, text: &str) -> Result<(u32, u32), Error> {
+        Ok(self.layout(text, |_, _| {}))
+    }
+
+    fn line_height(&self) -> u32 {
+        self.line_height
+    }
+
+    fn render(&self, text: &str, color: Color, _resources: &mut dyn Resources<'tx>) -> Result<RenderedText<'tx>, Error> {
+        let mut quads = Vec::new();
+        let mut missing_page = None;
+        let (width, height) = self.layout(text, |glyph, pen| {
+            let rect = match glyph.rect {
+                Some(rect) => rect,
+                None => return,
+            };
+            match self.pages.get(glyph.page) {
+                Some(texture) => quads.push(TextQuad {
+                    texture: texture.clone(),
+                    src: rect,
+                    dst: Rect::new(pen.x + glyph.offset.x, pen.y + glyph.offset.y, rect.width(), rect.height()),
+                }),
+                None => missing_page = Some(glyph.page),
+            }
+        });
+        if let Some(page) = missing_page {
+            return Err(Error::simple(format!("Bitmap font has no page {}", page)));
+        }
+        Ok(RenderedText { quads, tint: color, width, height })
+    }
+}
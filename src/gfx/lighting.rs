@@ -0,0 +1,144 @@
+use std::f32::consts::TAU;
+use std::rc::Rc;
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, RenderTarget};
+use sdl2::surface::Surface;
+
+use crate::{Error, Resources};
+use crate::gfx::animation::Ticker;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::texture::Texture;
+use crate::point::{FloatPoint, IntPoint};
+
+const GRADIENT_SIZE: u32 = 128;
+
+#[derive(Debug, Clone, Copy)]
+pub enum LightAnchor {
+    Character,
+    Tile(IntPoint),
+}
+
+pub struct Light {
+    pub anchor: LightAnchor,
+    pub color: Color,
+    pub radius: f32,
+    // Fraction of the radius and intensity lost at the bottom of a flicker
+    pub flicker_amplitude: f32,
+    // Flickers per second
+    pub flicker_speed: f32,
+}
+
+impl Light {
+    // Deterministic flicker made of two out of phase waves, between 1 - amplitude and 1
+    fn flicker(&self, seconds: f32, phase: f32) -> f32 {
+        if self.flicker_amplitude <= 0. {
+            return 1.;
+        }
+        let t = seconds * self.flicker_speed * TAU + phase;
+        let wave = (t.sin() + (t * 2.3 + phase * 1.7).sin()) / 2.;
+        1. - self.flicker_amplitude * (0.5 + 0.5 * wave)
+    }
+}
+
+fn scale_color(color: Color, factor: f32) -> Color {
+    Color::RGB((color.r as f32 * factor) as u8, (color.g as f32 * factor) as u8, (color.b as f32 * factor) as u8)
+}
+
+// White radial gradient, fading to black at the edge
+fn gradient_surface() -> Result<Surface<'static>, Error> {
+    let mut surface = Surface::new(GRADIENT_SIZE, GRADIENT_SIZE, PixelFormatEnum::RGBA32)?;
+    let pitch = surface.pitch() as usize;
+    let center = GRADIENT_SIZE as f32 / 2.;
+    surface.with_lock_mut(|pixels| {
+        for y in 0..GRADIENT_SIZE as usize {
+            for x in 0..GRADIENT_SIZE as usize {
+                let dx = (x as f32 + 0.5 - center) / center;
+                let dy = (y as f32 + 0.5 - center) / center;
+                let falloff = (1. - (dx * dx + dy * dy).sqrt()).max(0.);
+                let value = (falloff * falloff * 255.) as u8;
+                pixels[y * pitch + x * 4..][..4].copy_from_slice(&[value, value, value, 255]);
+            }
+        }
+    });
+    Ok(surface)
+}
+
+// Darkens the scene to an ambient colour except around lights. Lights are added into a
+// light map, which is then multiplied over whatever was drawn before.
+pub struct Lighting<'tx> {
+    ambient: Color,
+    lights: Vec<Light>,
+    ticks: u32,
+    gradient: Option<Rc<Texture<'tx>>>,
+    light_map: Option<Texture<'tx>>,
+}
+
+impl<'tx> Lighting<'tx> {
+    pub fn new(ambient: Color, lights: Vec<Light>) -> Self {
+        Lighting { ambient, lights, ticks: 0, gradient: None, light_map: None }
+    }
+
+    pub fn set_ambient(&mut self, ambient: Color) {
+        self.ambient = ambient;
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    // camera is the map position at the top left of the view, positions are in map pixels
+    pub fn draw<T: RenderTarget>(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'tx>,
+                                 camera: IntPoint, character: FloatPoint, tile_size: (u32, u32)) -> Result<(), Error> {
+        let (width, height) = renderer.view_size();
+        if self.light_map.as_ref().map(|t| (t.width(), t.height())) != Some((width, height)) {
+            let mut light_map = resources.create_target_texture(width, height)?;
+            light_map.texture_mut().set_blend_mode(BlendMode::Mod);
+            self.light_map = Some(light_map);
+        }
+        if self.gradient.is_none() {
+            let mut gradient = resources.texture_from_surface(gradient_surface()?)?;
+            gradient.texture_mut().set_blend_mode(BlendMode::Add);
+            self.gradient = Some(Rc::new(gradient));
+        }
+
+        let gradient = self.gradient.clone().unwrap();
+        let light_map = self.light_map.as_mut().unwrap();
+        let seconds = self.ticks as f32 / 1000.;
+        let ambient = self.ambient;
+        let lights = &self.lights;
+        renderer.with_target_texture(light_map.texture_mut(), |renderer| {
+            renderer.set_draw_color(ambient);
+            renderer.clear();
+            for (index, light) in lights.iter().enumerate() {
+                let position = match light.anchor {
+                    LightAnchor::Character => character,
+                    LightAnchor::Tile(tile) => FloatPoint::new(
+                        (tile.x as f32 + 0.5) * tile_size.0 as f32,
+                        (tile.y as f32 + 0.5) * tile_size.1 as f32,
+                    ),
+                };
+                let intensity = light.flicker(seconds, index as f32);
+                let radius = (light.radius * intensity).max(1.);
+                let center = position.truncate() - camera;
+                let dst = Rect::new(center.x - radius as i32, center.y - radius as i32, (radius * 2.) as u32, (radius * 2.) as u32);
+                renderer.copy_tinted(&gradient, None, dst, scale_color(light.color, intensity))?;
+            }
+            Ok(())
+        })?;
+
+        let light_map = self.light_map.as_ref().unwrap();
+        renderer.copy(light_map, None, Rect::new(0, 0, width, height))
+    }
+}
+
+impl<'tx> Ticker for Lighting<'tx> {
+    fn advance(&mut self, ticks: u32) {
+        self.ticks = self.ticks.wrapping_add(ticks);
+    }
+
+    fn reset(&mut self) {
+        self.ticks = 0;
+    }
+}
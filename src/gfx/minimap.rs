@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::RenderTarget;
+use sdl2::surface::Surface;
+
+use crate::{Error, Resources};
+use crate::gfx::renderer::Renderer;
+use crate::gfx::texture::Texture;
+use crate::gfx::tileset::TILE_INDEX_MASK;
+use crate::point::{FloatPoint, IntPoint};
+use crate::scene::map::Tiles;
+
+// Tiles whose average alpha is below this are considered empty and show the layers below
+const MIN_VISIBLE_ALPHA: u8 = 128;
+const CHARACTER_COLOR: Color = Color::RGB(255, 255, 255);
+
+// Average colour of every tile in a tileset image, indexed like Tileset::get_tile
+pub fn sample_tile_colors(surface: &Surface, tile_width: u32, tile_height: u32) -> Result<HashMap<u32, Color>, Error> {
+    if tile_width == 0 || tile_height == 0 {
+        return Err(Error::simple(format!("Tiles of {}x{} can not be sampled", tile_width, tile_height)));
+    }
+    let surface = surface.convert_format(PixelFormatEnum::RGBA32)?;
+    let columns = surface.width() / tile_width;
+    let rows = surface.height() / tile_height;
+    let pitch = surface.pitch() as usize;
+    let pixels = surface.without_lock().ok_or_else(|| Error::simple("Tileset surface must not need locking"))?;
+
+    let mut colors = HashMap::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let (mut r, mut g, mut b, mut a) = (0u64, 0u64, 0u64, 0u64);
+            for y in row * tile_height..(row + 1) * tile_height {
+                for x in column * tile_width..(column + 1) * tile_width {
+                    let pixel = &pixels[y as usize * pitch + x as usize * 4..][..4];
+                    let alpha = pixel[3] as u64;
+                    r += pixel[0] as u64 * alpha;
+                    g += pixel[1] as u64 * alpha;
+                    b += pixel[2] as u64 * alpha;
+                    a += alpha;
+                }
+            }
+            let color = match (r.checked_div(a), g.checked_div(a), b.checked_div(a)) {
+                (Some(r), Some(g), Some(b)) => Color::RGBA(r as u8, g as u8, b as u8, (a / (tile_width * tile_height) as u64) as u8),
+                _ => Color::RGBA(0, 0, 0, 0),
+            };
+            colors.insert(row * columns + column, color);
+        }
+    }
+    Ok(colors)
+}
+
+pub struct PointOfInterest {
+    pub tile: IntPoint,
+    pub color: Color,
+}
+
+pub struct Minimap<'tx> {
+    colors: HashMap<u32, Color>,
+    scale: u32,
+    position: IntPoint,
+    points_of_interest: Vec<PointOfInterest>,
+    visible: bool,
+    texture: Option<Texture<'tx>>,
+}
+
+impl<'tx> Minimap<'tx> {
+    pub fn new(colors: HashMap<u32, Color>, scale: u32, position: IntPoint, points_of_interest: Vec<PointOfInterest>, visible: bool) -> Self {
+        Minimap { colors, scale: scale.max(1), position, points_of_interest, visible, texture: None }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    // Forces the cached texture to be rebuilt, for when the tiles change
+    pub fn invalidate(&mut self) {
+        self.texture = None;
+    }
+
+    fn cell_color(&self, tiles: &Tiles<u32>, x: usize, y: usize) -> Option<Color> {
+        tiles.iter().rev()
+            .filter_map(|layer| layer.get(y).and_then(|row| row.get(x)))
+            .filter_map(|tile| self.colors.get(&(tile & TILE_INDEX_MASK)))
+            .find(|color| color.a >= MIN_VISIBLE_ALPHA)
+            .map(|color| Color::RGB(color.r, color.g, color.b))
+    }
+
+    fn build(&self, tiles: &Tiles<u32>, resources: &mut dyn Resources<'tx>) -> Result<Texture<'tx>, Error> {
+        let height = tiles.iter().map(|layer| layer.len()).max().unwrap_or(0);
+        let width = tiles.iter().flat_map(|layer| layer.iter().map(|row| row.len())).max().unwrap_or(0);
+        let mut surface = Surface::new(width.max(1) as u32 * self.scale, height.max(1) as u32 * self.scale, PixelFormatEnum::RGBA32)?;
+        surface.fill_rect(None, Color::RGBA(0, 0, 0, 160))?;
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(color) = self.cell_color(tiles, x, y) {
+                    let cell = Rect::new(x as i32 * self.scale as i32, y as i32 * self.scale as i32, self.scale, self.scale);
+                    surface.fill_rect(cell, color)?;
+                }
+            }
+        }
+        resources.texture_from_surface(surface)
+    }
+
+    // character is given in tile units
+    pub fn draw<T: RenderTarget>(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'tx>, tiles: &Tiles<u32>, character: FloatPoint) -> Result<(), Error> {
+        if !self.visible {
+            return Ok(());
+        }
+        if self.texture.is_none() {
+            self.texture = Some(self.build(tiles, resources)?);
+        }
+        let texture = self.texture.as_ref().unwrap();
+        let scale = self.scale as i32;
+        let marker_size = self.scale.max(3);
+
+        // Clipped to the minimap, so the character marker does not spill over its edges
+        let viewport = Rect::new(self.position.x, self.position.y, texture.width(), texture.height());
+        renderer.with_viewport(viewport, |renderer| {
+            renderer.copy(texture, None, Rect::new(0, 0, texture.width(), texture.height()))?;
+            for point in &self.points_of_interest {
+                renderer.set_draw_color(point.color);
+                renderer.fill_rect(Rect::new(point.tile.x * scale, point.tile.y * scale, self.scale, self.scale))?;
+            }
+            let center = (character * self.scale as f32).truncate();
+            renderer.set_draw_color(CHARACTER_COLOR);
+            renderer.fill_rect(Rect::new(center.x - marker_size as i32 / 2, center.y - marker_size as i32 / 2, marker_size, marker_size))
+        })
+    }
+}
@@ -5,20 +5,36 @@ use sdl2::rect::Rect;
 use crate::gfx::texture::Texture;
 
 pub mod animation;
+pub mod capture;
+pub mod font;
+pub mod lighting;
+pub mod minimap;
+pub mod particles;
+pub mod plane;
 pub mod renderer;
 pub mod spritesheet;
 pub mod texture;
 pub mod tileset;
+pub mod weather;
+pub mod window;
 
 
 pub struct TextureRect<'tx> {
     texture: Rc<Texture<'tx>>,
     rect: Rect,
+    flip_horizontal: bool,
+    flip_vertical: bool,
+    // Swaps the x and y axes, done before the other flips
+    flip_diagonal: bool,
 }
 
 impl<'tx> TextureRect<'tx> {
     fn new(texture: Rc<Texture<'tx>>, rect: Rect) -> Self {
-        TextureRect { texture, rect }
+        TextureRect { texture, rect, flip_horizontal: false, flip_vertical: false, flip_diagonal: false }
+    }
+
+    pub fn with_flip(self, flip_horizontal: bool, flip_vertical: bool, flip_diagonal: bool) -> Self {
+        TextureRect { flip_horizontal, flip_vertical, flip_diagonal, ..self }
     }
 
     pub fn texture(&self) -> &Texture<'tx> {
@@ -36,4 +52,20 @@ impl<'tx> TextureRect<'tx> {
     pub fn height(&self) -> u32 {
         self.rect.height()
     }
-}
\ No newline at end of file
+
+    pub fn flip_horizontal(&self) -> bool {
+        self.flip_horizontal
+    }
+
+    pub fn flip_vertical(&self) -> bool {
+        self.flip_vertical
+    }
+
+    pub fn flip_diagonal(&self) -> bool {
+        self.flip_diagonal
+    }
+
+    pub fn is_flipped(&self) -> bool {
+        self.flip_horizontal || self.flip_vertical || self.flip_diagonal
+    }
+}
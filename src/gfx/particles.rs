@@ -0,0 +1,141 @@
+use std::rc::Rc;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::RenderTarget;
+
+use crate::{Error, Point, SpriteSheet};
+use crate::data::gfx::RangeData;
+use crate::gfx::animation::Ticker;
+use crate::gfx::renderer::Renderer;
+use crate::point::FloatPoint;
+use crate::random::Random;
+
+pub struct EmitterConfig<'tx> {
+    pub sheet: Rc<SpriteSheet<'tx>>,
+    pub frames: Vec<u32>,
+    pub spawn_rate: f32,
+    pub max_particles: usize,
+    pub lifetime: RangeData,
+    pub velocity_x: RangeData,
+    pub velocity_y: RangeData,
+    pub gravity: FloatPoint,
+    pub start_color: Color,
+    pub end_color: Color,
+}
+
+struct Particle {
+    position: FloatPoint,
+    velocity: FloatPoint,
+    age: u32,
+    lifetime: u32,
+}
+
+impl Particle {
+    fn life_fraction(&self) -> f32 {
+        self.age as f32 / self.lifetime.max(1) as f32
+    }
+}
+
+pub struct Emitter<'tx> {
+    config: Rc<EmitterConfig<'tx>>,
+    position: FloatPoint,
+    particles: Vec<Particle>,
+    spawn_accumulator: f32,
+    emitting: bool,
+    random: Random,
+}
+
+fn lerp_channel(from: u8, to: u8, fraction: f32) -> u8 {
+    (from as f32 + (to as f32 - from as f32) * fraction).round() as u8
+}
+
+fn lerp_color(from: Color, to: Color, fraction: f32) -> Color {
+    Color::RGBA(
+        lerp_channel(from.r, to.r, fraction),
+        lerp_channel(from.g, to.g, fraction),
+        lerp_channel(from.b, to.b, fraction),
+        lerp_channel(from.a, to.a, fraction),
+    )
+}
+
+impl<'tx> Emitter<'tx> {
+    pub fn new(config: Rc<EmitterConfig<'tx>>, position: FloatPoint, seed: u32) -> Self {
+        Emitter { config, position, particles: Vec::new(), spawn_accumulator: 0., emitting: true, random: Random::new(seed) }
+    }
+
+    pub fn position(&self) -> FloatPoint {
+        self.position
+    }
+
+    pub fn move_to(&mut self, position: FloatPoint) {
+        self.position = position;
+    }
+
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    fn spawn(&mut self) {
+        let config = &self.config;
+        let lifetime = self.random.range(config.lifetime.min, config.lifetime.max).max(1.) as u32;
+        let velocity = Point::new(
+            self.random.range(config.velocity_x.min, config.velocity_x.max),
+            self.random.range(config.velocity_y.min, config.velocity_y.max),
+        );
+        self.particles.push(Particle { position: self.position, velocity, age: 0, lifetime });
+    }
+
+    pub fn draw<T: RenderTarget>(&self, renderer: &mut Renderer<T>) -> Result<(), Error> {
+        if self.config.frames.is_empty() {
+            return Ok(());
+        }
+        let sheet = &self.config.sheet;
+        for particle in &self.particles {
+            let fraction = particle.life_fraction();
+            let frame_index = ((fraction * self.config.frames.len() as f32) as usize).min(self.config.frames.len() - 1);
+            let frame = self.config.frames[frame_index];
+            let sprite = sheet.get_sprite(frame % sheet.sheet_width(), frame / sheet.sheet_width())?;
+            let dst = Rect::new(
+                particle.position.x as i32 - sprite.width() as i32 / 2,
+                particle.position.y as i32 - sprite.height() as i32 / 2,
+                sprite.width(),
+                sprite.height(),
+            );
+            renderer.copy_tinted(sprite.texture(), sprite.rect(), dst, lerp_color(self.config.start_color, self.config.end_color, fraction))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'tx> Ticker for Emitter<'tx> {
+    fn advance(&mut self, ticks: u32) {
+        let seconds = ticks as f32 / 1000.;
+        let gravity = self.config.gravity;
+        self.particles.retain_mut(|particle| {
+            particle.age += ticks;
+            particle.velocity += gravity * seconds;
+            particle.position += particle.velocity * seconds;
+            particle.age < particle.lifetime
+        });
+
+        if self.emitting {
+            self.spawn_accumulator += self.config.spawn_rate * seconds;
+            while self.spawn_accumulator >= 1. {
+                self.spawn_accumulator -= 1.;
+                if self.particles.len() < self.config.max_particles {
+                    self.spawn();
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.particles.clear();
+        self.spawn_accumulator = 0.;
+    }
+}
@@ -0,0 +1,98 @@
+use std::rc::Rc;
+
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, RenderTarget};
+
+use crate::Error;
+use crate::gfx::animation::Ticker;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::texture::Texture;
+use crate::point::{FloatPoint, IntPoint};
+use crate::utils::positive_mod;
+
+// Full screen image layer, used for parallax backgrounds and fog overlays.
+// A scroll factor of 0 keeps it fixed on screen, 1 makes it move along with the map.
+pub struct Plane<'tx> {
+    texture: Rc<Texture<'tx>>,
+    scroll_factor: FloatPoint,
+    velocity: FloatPoint,
+    tiled: bool,
+    opacity: u8,
+    blend: BlendMode,
+    scroll: FloatPoint,
+}
+
+impl<'tx> Plane<'tx> {
+    pub fn new(texture: Rc<Texture<'tx>>, scroll_factor: FloatPoint, velocity: FloatPoint, tiled: bool, opacity: u8, blend: BlendMode) -> Self {
+        Plane { texture, scroll_factor, velocity, tiled, opacity, blend, scroll: FloatPoint::new(0., 0.) }
+    }
+
+    pub fn set_opacity(&mut self, opacity: u8) {
+        self.opacity = opacity;
+    }
+
+    // camera is the map position shown at the top left corner of the view
+    pub fn draw<T: RenderTarget>(&self, renderer: &mut Renderer<T>, camera: IntPoint) -> Result<(), Error> {
+        let (width, height) = (self.texture.width(), self.texture.height());
+        let origin = (self.scroll - FloatPoint::new(camera.x as f32 * self.scroll_factor.x, camera.y as f32 * self.scroll_factor.y)).truncate();
+        let tint = Color::RGBA(255, 255, 255, self.opacity);
+
+        self.texture.with_blend_mode(self.blend, |texture| {
+            if self.tiled {
+                let (view_width, view_height) = renderer.view_size();
+                let start_x = positive_mod(origin.x, width) as i32 - width as i32;
+                let start_y = positive_mod(origin.y, height) as i32 - height as i32;
+                for y in (start_y..view_height as i32).step_by(height as usize) {
+                    for x in (start_x..view_width as i32).step_by(width as usize) {
+                        renderer.copy_tinted(texture, None, Rect::new(x, y, width, height), tint)?;
+                    }
+                }
+                Ok(())
+            } else {
+                renderer.copy_tinted(texture, None, Rect::new(origin.x, origin.y, width, height), tint)
+            }
+        })
+    }
+}
+
+impl<'tx> Ticker for Plane<'tx> {
+    fn advance(&mut self, ticks: u32) {
+        self.scroll += self.velocity * (ticks as f32 / 1000.);
+        if self.tiled {
+            // Keep the offset small so it does not lose precision over long sessions
+            self.scroll.x %= self.texture.width() as f32;
+            self.scroll.y %= self.texture.height() as f32;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.scroll = FloatPoint::new(0., 0.);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::pixels::PixelFormatEnum;
+    use sdl2::surface::Surface;
+
+    use crate::gfx::texture::TextureLoader;
+
+    use super::*;
+
+    #[test]
+    fn planes_keep_the_texture_blend_mode() {
+        let mut canvas = Surface::new(8, 8, PixelFormatEnum::RGBA32).unwrap().into_canvas().unwrap();
+        let creator = canvas.texture_creator();
+        let mut texture = TextureLoader::new(&creator).texture_from_surface(Surface::new(4, 4, PixelFormatEnum::RGBA32).unwrap()).unwrap();
+        texture.texture_mut().set_blend_mode(BlendMode::None);
+        let texture = Rc::new(texture);
+
+        let mut renderer = Renderer::new(&mut canvas);
+        for tiled in [true, false] {
+            let plane = Plane::new(texture.clone(), FloatPoint::new(1., 1.), FloatPoint::new(0., 0.), tiled, 128, BlendMode::Add);
+            plane.draw(&mut renderer, IntPoint::new(0, 0)).unwrap();
+            assert_eq!(texture.texture().blend_mode(), BlendMode::None);
+        }
+    }
+}
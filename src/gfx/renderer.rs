@@ -1,26 +1,97 @@
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::{Canvas, RenderTarget, TextureCreator};
-use sdl2::video::{Window, WindowContext};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{Point as SdlPoint, Rect};
+use sdl2::render::{BlendMode, Canvas, RenderTarget, TextureCreator};
+use sdl2::surface::Surface;
+use sdl2::video::{FullscreenType, Window, WindowContext};
 
 use crate::error::Error;
+use crate::gfx::TextureRect;
 use crate::gfx::texture::{Texture};
 use crate::point::IntPoint;
 
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    // Degrees, clockwise
+    pub angle: f64,
+    // Rotation center relative to the destination rect, its center if None
+    pub pivot: Option<IntPoint>,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    // Scales the destination rect around its center
+    pub scale: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform { angle: 0., pivot: None, flip_horizontal: false, flip_vertical: false, scale: 1. }
+    }
+}
+
+impl Transform {
+    pub fn rotated(angle: f64) -> Self {
+        Transform { angle, ..Default::default() }
+    }
+
+    pub fn flipped(flip_horizontal: bool, flip_vertical: bool) -> Self {
+        Transform { flip_horizontal, flip_vertical, ..Default::default() }
+    }
+
+    pub fn scaled(scale: f32) -> Self {
+        Transform { scale, ..Default::default() }
+    }
+
+    // Tiled flips, where the diagonal flip swaps the axes before the others. SDL flips before it
+    // rotates, so the swap is a vertical flip followed by a quarter turn, and the horizontal and
+    // vertical flips trade places. Only square tiles keep their bounds when diagonally flipped.
+    pub fn tile_flip(flip_horizontal: bool, flip_vertical: bool, flip_diagonal: bool) -> Self {
+        if flip_diagonal {
+            Transform { angle: 90., ..Transform::flipped(flip_vertical, !flip_horizontal) }
+        } else {
+            Transform::flipped(flip_horizontal, flip_vertical)
+        }
+    }
+
+    fn scale_rect(&self, rect: Rect) -> Rect {
+        if self.scale == 1. {
+            return rect;
+        }
+        let width = (rect.width() as f32 * self.scale).round().max(0.) as u32;
+        let height = (rect.height() as f32 * self.scale).round().max(0.) as u32;
+        let center = rect.center();
+        Rect::from_center(center, width, height)
+    }
+
+    // The pivot is relative to the destination rect, so it follows its scale
+    fn scale_pivot(&self) -> Option<SdlPoint> {
+        self.pivot.map(|pivot| SdlPoint::new((pivot.x as f32 * self.scale).round() as i32, (pivot.y as f32 * self.scale).round() as i32))
+    }
+}
+
 pub struct Renderer<'canvas, T: RenderTarget> {
     canvas: &'canvas mut Canvas<T>,
     offset: IntPoint,
+    // Current clip rect in canvas coordinates
+    clip: Option<Rect>,
 }
 
 impl<'canvas, T: RenderTarget> Renderer<'canvas, T> {
     pub fn new(canvas: &'canvas mut Canvas<T>) -> Self {
-        Renderer { canvas, offset: IntPoint::new(0, 0) }
+        let clip = canvas.clip_rect();
+        Renderer { canvas, offset: IntPoint::new(0, 0), clip }
     }
 
     pub fn set_draw_color<C: Into<Color>>(&mut self, color: C) {
         self.canvas.set_draw_color(color)
     }
 
+    pub fn set_blend_mode(&mut self, blend: BlendMode) {
+        self.canvas.set_blend_mode(blend)
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        self.canvas.blend_mode()
+    }
+
     pub fn clear(&mut self) {
         self.canvas.clear();
     }
@@ -29,6 +100,36 @@ impl<'canvas, T: RenderTarget> Renderer<'canvas, T> {
         self.canvas.present();
     }
 
+    // Size of the area that can currently be drawn to, taking viewports into account
+    pub fn view_size(&self) -> (u32, u32) {
+        self.visible_area().size()
+    }
+
+    // Where the local origin is on the render target. Scenes keep it from their last draw to map mouse positions back.
+    pub fn offset(&self) -> IntPoint {
+        self.offset
+    }
+
+    pub fn output_size(&self) -> Result<(u32, u32), Error> {
+        Ok(self.canvas.output_size()?)
+    }
+
+    // Copies the current target pixels into a surface. This is slow, only meant for captures and tests
+    pub fn read_surface(&self) -> Result<Surface<'static>, Error> {
+        let viewport = self.canvas.viewport();
+        let format = PixelFormatEnum::RGBA32;
+        let pixels = self.canvas.read_pixels(Rect::new(0, 0, viewport.width(), viewport.height()), format)?;
+        let mut surface = Surface::new(viewport.width(), viewport.height(), format)?;
+        let row_length = viewport.width() as usize * format.byte_size_per_pixel();
+        let pitch = surface.pitch() as usize;
+        surface.with_lock_mut(|data| {
+            for (row, source) in pixels.chunks_exact(row_length).enumerate() {
+                data[row * pitch..row * pitch + row_length].copy_from_slice(source);
+            }
+        });
+        Ok(surface)
+    }
+
     pub fn copy<R1, R2>(&mut self, texture: &Texture, src: R1, dst: R2) -> Result<(), Error>
         where
             R1: Into<Option<Rect>>,
@@ -43,6 +144,143 @@ impl<'canvas, T: RenderTarget> Renderer<'canvas, T> {
         Ok(self.canvas.copy(texture.texture(), src, new_dst)?)
     }
 
+    pub fn copy_ex<R1, R2>(&mut self, texture: &Texture, src: R1, dst: R2, transform: &Transform) -> Result<(), Error>
+        where
+            R1: Into<Option<Rect>>,
+            R2: Into<Option<Rect>>,
+    {
+        let dst = dst.into().map(|rect| transform.scale_rect(rect));
+        let new_dst = dst.map(|rect| self.offset_rect(rect));
+        Ok(self.canvas.copy_ex(texture.texture(), src, new_dst, transform.angle, transform.scale_pivot(), transform.flip_horizontal, transform.flip_vertical)?)
+    }
+
+    pub fn copy_texture_rect(&mut self, texture_rect: &TextureRect, dst: Rect) -> Result<(), Error> {
+        if texture_rect.is_flipped() {
+            let transform = Transform::tile_flip(texture_rect.flip_horizontal(), texture_rect.flip_vertical(), texture_rect.flip_diagonal());
+            self.copy_ex(texture_rect.texture(), texture_rect.rect(), dst, &transform)
+        } else {
+            self.copy(texture_rect.texture(), texture_rect.rect(), dst)
+        }
+    }
+
+    pub fn copy_tinted<R1, R2>(&mut self, texture: &Texture, src: R1, dst: R2, color: Color) -> Result<(), Error>
+        where
+            R1: Into<Option<Rect>>,
+            R2: Into<Option<Rect>>,
+    {
+        texture.with_modulation(color, |texture| self.copy(texture, src, dst))
+    }
+
+
+    fn offset_rect(&self, rect: Rect) -> Rect {
+        Rect::new(rect.x() + self.offset.x, rect.y() + self.offset.y, rect.width(), rect.height())
+    }
+
+    fn offset_point(&self, point: IntPoint) -> SdlPoint {
+        SdlPoint::new(point.x + self.offset.x, point.y + self.offset.y)
+    }
+
+    // Area that can actually be drawn to, in canvas coordinates
+    fn visible_area(&self) -> Rect {
+        let viewport = self.canvas.viewport();
+        let area = Rect::new(0, 0, viewport.width(), viewport.height());
+        self.clip
+            .and_then(|clip| clip.intersection(area))
+            .unwrap_or(area)
+    }
+
+    fn visible_rects(&self, rects: &[Rect]) -> Vec<Rect> {
+        let area = self.visible_area();
+        rects.iter()
+            .map(|rect| self.offset_rect(*rect))
+            .filter(|rect| rect.has_intersection(area))
+            .collect()
+    }
+
+    pub fn draw_rect(&mut self, rect: Rect) -> Result<(), Error> {
+        self.draw_rects(&[rect])
+    }
+
+    pub fn draw_rects(&mut self, rects: &[Rect]) -> Result<(), Error> {
+        let visible = self.visible_rects(rects);
+        if visible.is_empty() {
+            return Ok(());
+        }
+        Ok(self.canvas.draw_rects(&visible)?)
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect) -> Result<(), Error> {
+        self.fill_rects(&[rect])
+    }
+
+    pub fn fill_rects(&mut self, rects: &[Rect]) -> Result<(), Error> {
+        let visible = self.visible_rects(rects);
+        if visible.is_empty() {
+            return Ok(());
+        }
+        Ok(self.canvas.fill_rects(&visible)?)
+    }
+
+    pub fn draw_line(&mut self, start: IntPoint, end: IntPoint) -> Result<(), Error> {
+        Ok(self.canvas.draw_line(self.offset_point(start), self.offset_point(end))?)
+    }
+
+    // Draws a connected line through all points
+    pub fn draw_lines(&mut self, points: &[IntPoint]) -> Result<(), Error> {
+        let sdl_points: Vec<SdlPoint> = points.iter().map(|point| self.offset_point(*point)).collect();
+        Ok(self.canvas.draw_lines(sdl_points.as_slice())?)
+    }
+
+    pub fn draw_points(&mut self, points: &[IntPoint]) -> Result<(), Error> {
+        let area = self.visible_area();
+        let sdl_points: Vec<SdlPoint> = points.iter()
+            .map(|point| self.offset_point(*point))
+            .filter(|point| area.contains_point(*point))
+            .collect();
+        if sdl_points.is_empty() {
+            return Ok(());
+        }
+        Ok(self.canvas.draw_points(sdl_points.as_slice())?)
+    }
+
+    pub fn draw_polygon(&mut self, points: &[IntPoint]) -> Result<(), Error> {
+        if let Some(first) = points.first() {
+            let mut closed = points.to_vec();
+            closed.push(*first);
+            self.draw_lines(&closed)?;
+        }
+        Ok(())
+    }
+
+    pub fn fill_polygon(&mut self, points: &[IntPoint]) -> Result<(), Error> {
+        if points.len() < 3 {
+            return Ok(());
+        }
+        let area = self.visible_area();
+        let spans = polygon_spans(points, area.top() - self.offset.y, area.bottom() - self.offset.y);
+        self.fill_rects(&spans)
+    }
+
+    pub fn draw_circle(&mut self, center: IntPoint, radius: i32) -> Result<(), Error> {
+        let mut points = Vec::new();
+        for (x, y) in circle_octant(radius) {
+            for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y), (-x, -y), (-y, -x), (y, -x), (x, -y)] {
+                points.push(IntPoint::new(center.x + dx, center.y + dy));
+            }
+        }
+        self.draw_points(&points)
+    }
+
+    pub fn fill_circle(&mut self, center: IntPoint, radius: i32) -> Result<(), Error> {
+        // One span per row so translucent fills do not blend twice
+        let spans: Vec<Rect> = (-radius..=radius)
+            .map(|dy| {
+                let half_width = ((radius * radius - dy * dy) as f32).sqrt() as i32;
+                Rect::new(center.x - half_width, center.y + dy, (2 * half_width + 1) as u32, 1)
+            })
+            .collect();
+        self.fill_rects(&spans)
+    }
 
     pub fn with_offset<F>(&mut self, offset: IntPoint, func: F) -> Result<(), Error>
         where F: FnOnce(&mut Renderer<T>) -> Result<(), Error>,
@@ -53,16 +291,89 @@ impl<'canvas, T: RenderTarget> Renderer<'canvas, T> {
         result
     }
 
+    // Runs func with the origin moved to the viewport corner and drawing clipped to it.
+    // The viewport is relative to the current offset and nests inside any enclosing viewport
+    pub fn with_viewport<F>(&mut self, viewport: Rect, func: F) -> Result<(), Error>
+        where F: FnOnce(&mut Renderer<T>) -> Result<(), Error>,
+    {
+        let area = self.offset_rect(viewport);
+        let clip = match self.clip {
+            Some(current) => match current.intersection(area) {
+                Some(clip) => clip,
+                None => return Ok(()),
+            },
+            None => area,
+        };
+
+        let previous_clip = self.clip;
+        self.clip = Some(clip);
+        self.canvas.set_clip_rect(clip);
+        let result = self.with_offset(IntPoint::new(viewport.x(), viewport.y()), func);
+        self.clip = previous_clip;
+        self.canvas.set_clip_rect(previous_clip);
+        result
+    }
+
     pub fn with_target_texture<'r, F>(&mut self, texture: &mut sdl2::render::Texture<'r>, render_function: F) -> Result<(), Error>
         where F: FnOnce(&mut Renderer<T>) -> Result<(), Error> {
+        let mut result = Ok(());
         self.canvas.with_texture_canvas(texture, |canvas| {
             let mut renderer = Renderer::new(canvas);
-            render_function(&mut renderer).unwrap();
+            result = render_function(&mut renderer);
         })?;
-        Ok(())
+        // Switching targets resets the clip rect, so the one for this renderer is restored
+        self.canvas.set_clip_rect(self.clip);
+        result
     }
 }
 
+// Scanline fill with the even-odd rule, sampling each row at its center. Rows outside min_y..max_y are skipped.
+fn polygon_spans(points: &[IntPoint], min_y: i32, max_y: i32) -> Vec<Rect> {
+    let min_y = points.iter().map(|p| p.y).min().unwrap_or(0).max(min_y);
+    let max_y = points.iter().map(|p| p.y).max().unwrap_or(0).min(max_y);
+    let mut spans = Vec::new();
+    for y in min_y..max_y {
+        let scan_y = y as f32 + 0.5;
+        let mut crossings: Vec<f32> = Vec::new();
+        for (index, start) in points.iter().enumerate() {
+            let end = points[(index + 1) % points.len()];
+            let (y0, y1) = (start.y as f32, end.y as f32);
+            if (y0 <= scan_y && y1 > scan_y) || (y1 <= scan_y && y0 > scan_y) {
+                crossings.push(start.x as f32 + (scan_y - y0) / (y1 - y0) * (end.x - start.x) as f32);
+            }
+        }
+        crossings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for pair in crossings.chunks_exact(2) {
+            // Pixels whose center is inside, so a crossing halfway through a pixel is not counted twice
+            let x0 = (pair[0] - 0.5).ceil() as i32;
+            let x1 = (pair[1] - 0.5).ceil() as i32;
+            if x1 > x0 {
+                spans.push(Rect::new(x0, y, (x1 - x0) as u32, 1));
+            }
+        }
+    }
+    spans
+}
+
+// Midpoint circle algorithm, returns the (x, y) pairs of the first octant
+fn circle_octant(radius: i32) -> Vec<(i32, i32)> {
+    let mut points = Vec::new();
+    let mut x = radius.max(0);
+    let mut y = 0;
+    let mut error = 1 - x;
+    while x >= y {
+        points.push((x, y));
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+    points
+}
+
 pub struct BackBuffer<'sdl> {
     canvas: Canvas<Window>,
     back_buffer: sdl2::render::Texture<'sdl>,
@@ -95,4 +406,200 @@ impl<'sdl> BackBuffer<'sdl> {
         self.canvas.present();
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.canvas.window().fullscreen_state() != FullscreenType::Off
+    }
+
+    // Uses the desktop resolution, the back buffer keeps its size and is stretched to the window
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), Error> {
+        self.canvas.window_mut().set_fullscreen(if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off })?;
+        Ok(())
+    }
+
+    pub fn window_size(&self) -> (u32, u32) {
+        self.canvas.window().size()
+    }
+
+    pub fn view_size(&self) -> (u32, u32) {
+        let query = self.back_buffer.query();
+        (query.width, query.height)
+    }
+
+    pub fn read_surface(&mut self) -> Result<Surface<'static>, Error> {
+        let mut result = None;
+        self.canvas.with_texture_canvas(&mut self.back_buffer, |canvas| {
+            result = Some(Renderer::new(canvas).read_surface());
+        })?;
+        result.unwrap_or_else(|| Err(Error::simple("Back buffer was not read")))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use crate::gfx::texture::TextureLoader;
+
+    use super::*;
+
+    fn pixel(surface: &Surface, x: usize, y: usize) -> Color {
+        let pitch = surface.pitch() as usize;
+        let pixels = surface.without_lock().unwrap();
+        let pixel = &pixels[y * pitch + x * 4..][..4];
+        Color::RGBA(pixel[0], pixel[1], pixel[2], pixel[3])
+    }
+
+    // Where the pixel at x, y of an n by n tile lands once drawn like SDL does: flipped, then turned clockwise
+    fn sdl_copy(transform: &Transform, n: i32, (mut x, mut y): (i32, i32)) -> (i32, i32) {
+        if transform.flip_horizontal { x = n - 1 - x; }
+        if transform.flip_vertical { y = n - 1 - y; }
+        for _ in 0..(transform.angle as i32 / 90) {
+            (x, y) = (n - 1 - y, x);
+        }
+        (x, y)
+    }
+
+    #[test]
+    fn tile_flips_match_tiled() {
+        let n = 3;
+        for flags in 0..8 {
+            let (horizontal, vertical, diagonal) = (flags & 1 != 0, flags & 2 != 0, flags & 4 != 0);
+            let transform = Transform::tile_flip(horizontal, vertical, diagonal);
+            for (x, y) in (0..n).flat_map(|x| (0..n).map(move |y| (x, y))) {
+                // Tiled swaps the axes first, then flips
+                let (mut tx, mut ty) = if diagonal { (y, x) } else { (x, y) };
+                if horizontal { tx = n - 1 - tx; }
+                if vertical { ty = n - 1 - ty; }
+                assert_eq!(sdl_copy(&transform, n, (x, y)), (tx, ty), "flags {:03b} at {}, {}", flags, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn pivot_follows_scale() {
+        let transform = Transform { pivot: Some(IntPoint::new(4, 7)), ..Transform::scaled(2.) };
+        assert_eq!(transform.scale_rect(Rect::new(10, 10, 8, 8)), Rect::new(6, 6, 16, 16));
+        assert_eq!(transform.scale_pivot(), Some(SdlPoint::new(8, 14)));
+        assert_eq!(Transform::scaled(2.).scale_pivot(), None);
+    }
+
+    fn points(coordinates: &[(i32, i32)]) -> Vec<IntPoint> {
+        coordinates.iter().map(|(x, y)| IntPoint::new(*x, *y)).collect()
+    }
+
+    fn area(spans: &[Rect]) -> u32 {
+        spans.iter().map(|span| span.width() * span.height()).sum()
+    }
+
+    #[test]
+    fn polygon_edges() {
+        // Left and top edges are in, right and bottom edges out, so shapes that share an edge do not overlap
+        let square = points(&[(0, 0), (4, 0), (4, 4), (0, 4)]);
+        assert_eq!(polygon_spans(&square, i32::MIN, i32::MAX), (0..4).map(|y| Rect::new(0, y, 4, 1)).collect::<Vec<_>>());
+        let next = points(&[(4, 0), (8, 0), (8, 4), (4, 4)]);
+        assert_eq!(polygon_spans(&next, i32::MIN, i32::MAX)[0], Rect::new(4, 0, 4, 1));
+
+        // Winding does not matter
+        let reversed: Vec<IntPoint> = square.iter().rev().copied().collect();
+        assert_eq!(polygon_spans(&reversed, i32::MIN, i32::MAX), polygon_spans(&square, i32::MIN, i32::MAX));
+
+        // Rows outside the visible range are skipped
+        assert_eq!(polygon_spans(&square, 1, 3), vec![Rect::new(0, 1, 4, 1), Rect::new(0, 2, 4, 1)]);
+        assert!(polygon_spans(&square, 10, 20).is_empty());
+
+        // Flat or empty shapes fill nothing
+        assert!(polygon_spans(&points(&[(0, 0), (4, 0), (8, 0)]), i32::MIN, i32::MAX).is_empty());
+        assert!(polygon_spans(&points(&[(0, 0), (4, 4), (8, 8)]), i32::MIN, i32::MAX).is_empty());
+        assert!(polygon_spans(&[], i32::MIN, i32::MAX).is_empty());
+    }
+
+    #[test]
+    fn polygon_even_odd() {
+        // Only pixels whose center is inside are filled, the diagonal of this triangle crosses its row centers halfway through a pixel
+        let triangle = points(&[(0, 0), (8, 8), (0, 8)]);
+        assert_eq!(area(&polygon_spans(&triangle, i32::MIN, i32::MAX)), 28);
+
+        // A star drawn in one stroke leaves its center pentagon empty
+        let star = points(&[(50, 0), (79, 90), (2, 35), (97, 35), (21, 90)]);
+        let spans = polygon_spans(&star, i32::MIN, i32::MAX);
+        let filled = |x: i32, y: i32| spans.iter().any(|span| span.contains_point((x, y)));
+        assert!(!filled(50, 50));
+        assert!(filled(50, 10));
+        assert!(filled(10, 37));
+    }
+
+    #[test]
+    fn circle_octants() {
+        assert_eq!(circle_octant(0), vec![(0, 0)]);
+        assert_eq!(circle_octant(-3), vec![(0, 0)]);
+        assert_eq!(circle_octant(1), vec![(1, 0)]);
+        for radius in 1..50 {
+            let octant = circle_octant(radius);
+            assert_eq!(octant[0], (radius, 0));
+            for (index, (x, y)) in octant.iter().enumerate() {
+                // One point per row, moving in at most one pixel, within half a pixel of the circle
+                assert_eq!(*y, index as i32);
+                assert!(x >= y);
+                let distance = ((x * x + y * y) as f32).sqrt();
+                assert!((distance - radius as f32).abs() <= 0.5, "radius {} at {}, {}", radius, x, y);
+            }
+            assert!(octant.windows(2).all(|pair| pair[0].0 - pair[1].0 <= 1));
+            // The octant ends at the diagonal
+            let (x, y) = octant[octant.len() - 1];
+            assert!(x - y <= 1);
+        }
+    }
+
+    #[test]
+    fn tints_do_not_last() {
+        let mut canvas = Surface::new(4, 1, PixelFormatEnum::RGBA32).unwrap().into_canvas().unwrap();
+        let creator = canvas.texture_creator();
+        let mut white = Surface::new(1, 1, PixelFormatEnum::RGBA32).unwrap();
+        white.fill_rect(None, Color::WHITE).unwrap();
+        let mut texture = TextureLoader::new(&creator).texture_from_surface(white).unwrap();
+        texture.texture_mut().set_color_mod(0, 0, 255);
+
+        let mut renderer = Renderer::new(&mut canvas);
+        renderer.set_draw_color(Color::BLACK);
+        renderer.clear();
+        renderer.copy_tinted(&texture, None, Rect::new(0, 0, 1, 1), Color::RED).unwrap();
+        renderer.copy(&texture, None, Rect::new(1, 0, 1, 1)).unwrap();
+        // Drawing inside the tint sees it, the texture keeps its own modulation
+        texture.with_modulation(Color::GREEN, |texture| renderer.copy(texture, None, Rect::new(2, 0, 1, 1))).unwrap();
+        assert_eq!(texture.modulation(), Color::BLUE);
+
+        let surface = renderer.read_surface().unwrap();
+        assert_eq!(pixel(&surface, 0, 0), Color::RED);
+        assert_eq!(pixel(&surface, 1, 0), Color::BLUE);
+        assert_eq!(pixel(&surface, 2, 0), Color::GREEN);
+        assert_eq!(pixel(&surface, 3, 0), Color::BLACK);
+    }
+
+    #[test]
+    fn viewports_clip_and_nest() {
+        let mut canvas = Surface::new(40, 40, PixelFormatEnum::RGBA32).unwrap().into_canvas().unwrap();
+        let mut renderer = Renderer::new(&mut canvas);
+        renderer.set_draw_color(Color::BLACK);
+        renderer.clear();
+        renderer.with_viewport(Rect::new(10, 10, 20, 20), |renderer| {
+            assert_eq!(renderer.view_size(), (20, 20));
+            renderer.set_draw_color(Color::RED);
+            renderer.fill_rect(Rect::new(-10, -10, 40, 40))?;
+            // Half outside of the outer viewport
+            renderer.with_viewport(Rect::new(15, 15, 10, 10), |renderer| {
+                renderer.set_draw_color(Color::GREEN);
+                renderer.fill_rect(Rect::new(0, 0, 10, 10))
+            })
+        }).unwrap();
+        // The clip is gone once the viewport is left
+        renderer.set_draw_color(Color::BLUE);
+        renderer.fill_rect(Rect::new(0, 0, 1, 1)).unwrap();
+
+        let surface = renderer.read_surface().unwrap();
+        assert_eq!(pixel(&surface, 0, 0), Color::BLUE);
+        assert_eq!(pixel(&surface, 9, 9), Color::BLACK);
+        assert_eq!(pixel(&surface, 10, 10), Color::RED);
+        assert_eq!(pixel(&surface, 24, 24), Color::RED);
+        assert_eq!(pixel(&surface, 25, 25), Color::GREEN);
+        assert_eq!(pixel(&surface, 29, 29), Color::GREEN);
+        assert_eq!(pixel(&surface, 30, 30), Color::BLACK);
+    }
+}
@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use sdl2::{image::LoadSurface, render::TextureCreator, surface::Surface};
+use sdl2::{image::LoadSurface, pixels::Color, render::{BlendMode, TextureCreator}, surface::Surface};
 
 use crate::Error;
 
@@ -15,7 +15,7 @@ impl<'r> Texture<'r> {
         &self.texture
     }
 
-    pub fn texture_mut(&mut self) -> &'r mut sdl2::render::Texture {
+    pub fn texture_mut(&mut self) -> &mut sdl2::render::Texture<'r> {
         &mut self.texture
     }
 
@@ -26,6 +26,51 @@ impl<'r> Texture<'r> {
     pub fn width(&self) -> u32 {
         self.width
     }
+
+    pub fn modulation(&self) -> Color {
+        let (r, g, b) = self.texture.color_mod();
+        Color::RGBA(r, g, b, self.texture.alpha_mod())
+    }
+
+    // Textures are shared through Rc, so the tint goes through the raw handle instead of &mut.
+    // It only lasts for draw, the texture gets its previous modulation back afterwards.
+    pub fn with_modulation<R>(&self, color: Color, draw: impl FnOnce(&Self) -> R) -> R {
+        let previous = self.modulation();
+        self.set_raw_modulation(color);
+        let result = draw(self);
+        self.set_raw_modulation(previous);
+        result
+    }
+
+    fn set_raw_modulation(&self, color: Color) {
+        unsafe {
+            sdl2::sys::SDL_SetTextureColorMod(self.texture.raw(), color.r, color.g, color.b);
+            sdl2::sys::SDL_SetTextureAlphaMod(self.texture.raw(), color.a);
+        }
+    }
+
+    // Like with_modulation, for textures drawn with another blend mode by one user, e.g. a plane
+    pub fn with_blend_mode<R>(&self, blend: BlendMode, draw: impl FnOnce(&Self) -> R) -> R {
+        let previous = self.texture.blend_mode();
+        self.set_raw_blend_mode(blend);
+        let result = draw(self);
+        self.set_raw_blend_mode(previous);
+        result
+    }
+
+    fn set_raw_blend_mode(&self, blend: BlendMode) {
+        let raw_blend = match blend {
+            BlendMode::None => sdl2::sys::SDL_BlendMode::SDL_BLENDMODE_NONE,
+            BlendMode::Blend => sdl2::sys::SDL_BlendMode::SDL_BLENDMODE_BLEND,
+            BlendMode::Add => sdl2::sys::SDL_BlendMode::SDL_BLENDMODE_ADD,
+            BlendMode::Mod => sdl2::sys::SDL_BlendMode::SDL_BLENDMODE_MOD,
+            BlendMode::Mul => sdl2::sys::SDL_BlendMode::SDL_BLENDMODE_MUL,
+            BlendMode::Invalid => sdl2::sys::SDL_BlendMode::SDL_BLENDMODE_INVALID,
+        };
+        unsafe {
+            sdl2::sys::SDL_SetTextureBlendMode(self.texture.raw(), raw_blend);
+        }
+    }
 }
 
 pub struct TextureLoader<'ttf, T> {
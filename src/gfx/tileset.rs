@@ -4,6 +4,12 @@ use std::rc::Rc;
 use crate::{Error, SpriteSheet};
 use crate::gfx::TextureRect;
 
+// Tiled stores tile flips in the high bits of each tile index
+pub const FLIP_HORIZONTAL_FLAG: u32 = 0x8000_0000;
+pub const FLIP_VERTICAL_FLAG: u32 = 0x4000_0000;
+pub const FLIP_DIAGONAL_FLAG: u32 = 0x2000_0000;
+pub const TILE_INDEX_MASK: u32 = !(FLIP_HORIZONTAL_FLAG | FLIP_VERTICAL_FLAG | FLIP_DIAGONAL_FLAG);
+
 pub struct Tileset<'tx> {
     sheet: Rc<SpriteSheet<'tx>>,
 }
@@ -14,8 +20,10 @@ impl<'tx> Tileset<'tx> {
     }
 
     pub fn get_tile<T: TryInto<u32> + Debug + Copy>(&self, index: T) -> Result<TextureRect<'tx>, Error> {
-        let u32_index = index.try_into().map_err(|_| Error::simple(format!("Could not convert index {:?} into u32", index)))?;
-        self.sheet.get_sprite(u32_index % self.sheet.sheet_width(), u32_index / self.sheet.sheet_width())
+        let raw_index = index.try_into().map_err(|_| Error::simple(format!("Could not convert index {:?} into u32", index)))?;
+        let u32_index = raw_index & TILE_INDEX_MASK;
+        Ok(self.sheet.get_sprite(u32_index % self.sheet.sheet_width(), u32_index / self.sheet.sheet_width())?
+            .with_flip(raw_index & FLIP_HORIZONTAL_FLAG != 0, raw_index & FLIP_VERTICAL_FLAG != 0, raw_index & FLIP_DIAGONAL_FLAG != 0))
     }
 
     pub fn tile_width(&self) -> u32 {
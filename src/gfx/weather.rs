@@ -0,0 +1,252 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, RenderTarget};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::gfx::animation::Ticker;
+use crate::gfx::renderer::Renderer;
+use crate::point::FloatPoint;
+use crate::random::Random;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Storm,
+    Snow,
+    Sandstorm,
+}
+
+struct KindSettings {
+    // Particles on screen at full intensity
+    max_particles: usize,
+    // Pixels per second
+    velocity_x: (f32, f32),
+    velocity_y: (f32, f32),
+    color: Color,
+    // Overlay drawn over the whole view at full intensity
+    overlay: Color,
+}
+
+const RAIN: KindSettings = KindSettings {
+    max_particles: 300,
+    velocity_x: (-120., -80.),
+    velocity_y: (800., 1000.),
+    color: Color::RGBA(170, 190, 230, 170),
+    overlay: Color::RGBA(0, 0, 20, 70),
+};
+
+const STORM: KindSettings = KindSettings {
+    max_particles: 600,
+    velocity_x: (-350., -250.),
+    velocity_y: (1000., 1300.),
+    color: Color::RGBA(170, 190, 230, 190),
+    overlay: Color::RGBA(0, 0, 20, 120),
+};
+
+const SNOW: KindSettings = KindSettings {
+    max_particles: 250,
+    velocity_x: (-20., 20.),
+    velocity_y: (50., 90.),
+    color: Color::RGBA(255, 255, 255, 220),
+    overlay: Color::RGBA(20, 20, 40, 30),
+};
+
+const SANDSTORM: KindSettings = KindSettings {
+    max_particles: 500,
+    velocity_x: (-900., -700.),
+    velocity_y: (-30., 30.),
+    color: Color::RGBA(210, 170, 110, 200),
+    overlay: Color::RGBA(180, 140, 80, 90),
+};
+
+const FLASH_DURATION: u32 = 180;
+const FLASH_INTERVAL: (f32, f32) = (4000., 12000.);
+const DEFAULT_VIEW_SIZE: (u32, u32) = (1024, 768);
+
+impl WeatherKind {
+    fn settings(&self) -> Option<&'static KindSettings> {
+        match self {
+            WeatherKind::Clear => None,
+            WeatherKind::Rain => Some(&RAIN),
+            WeatherKind::Storm => Some(&STORM),
+            WeatherKind::Snow => Some(&SNOW),
+            WeatherKind::Sandstorm => Some(&SANDSTORM),
+        }
+    }
+}
+
+struct WeatherParticle {
+    position: FloatPoint,
+    velocity: FloatPoint,
+    phase: f32,
+}
+
+// Screen space weather overlay. Particles live in view coordinates, so they do not follow the camera.
+pub struct Weather {
+    kind: WeatherKind,
+    intensity: f32,
+    particles: Vec<WeatherParticle>,
+    random: Random,
+    view_size: (u32, u32),
+    ticks: u32,
+    next_flash: u32,
+    flash_ticks_left: u32,
+}
+
+fn scale_alpha(color: Color, factor: f32) -> Color {
+    Color::RGBA(color.r, color.g, color.b, (color.a as f32 * factor.clamp(0., 1.)) as u8)
+}
+
+impl Weather {
+    pub fn new(kind: WeatherKind, intensity: f32) -> Self {
+        let mut weather = Weather {
+            kind: WeatherKind::Clear,
+            intensity: 0.,
+            particles: Vec::new(),
+            random: Random::new(0x5EA7_4E12),
+            view_size: DEFAULT_VIEW_SIZE,
+            ticks: 0,
+            next_flash: 0,
+            flash_ticks_left: 0,
+        };
+        weather.set(kind, intensity);
+        weather
+    }
+
+    pub fn kind(&self) -> WeatherKind {
+        self.kind
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    // Intensity goes from 0 (nothing) to 1 (full)
+    pub fn set(&mut self, kind: WeatherKind, intensity: f32) {
+        if kind != self.kind {
+            self.particles.clear();
+            self.flash_ticks_left = 0;
+        }
+        self.kind = kind;
+        self.intensity = intensity.clamp(0., 1.);
+        self.schedule_flash();
+    }
+
+    pub fn flash(&mut self) {
+        self.flash_ticks_left = FLASH_DURATION;
+    }
+
+    fn schedule_flash(&mut self) {
+        // Stronger storms flash more often
+        let interval = self.random.range(FLASH_INTERVAL.0, FLASH_INTERVAL.1) / self.intensity.max(0.25);
+        self.next_flash = self.ticks.wrapping_add(interval as u32);
+    }
+
+    fn spawn(&mut self, settings: &KindSettings, anywhere: bool) {
+        let (width, height) = (self.view_size.0 as f32, self.view_size.1 as f32);
+        let velocity = FloatPoint::new(
+            self.random.range(settings.velocity_x.0, settings.velocity_x.1),
+            self.random.range(settings.velocity_y.0, settings.velocity_y.1),
+        );
+        let position = if anywhere {
+            FloatPoint::new(self.random.range(0., width), self.random.range(0., height))
+        } else if velocity.x < -velocity.y.abs() {
+            // Mostly horizontal particles come in from the right edge
+            FloatPoint::new(width, self.random.range(0., height))
+        } else {
+            FloatPoint::new(self.random.range(0., width * 1.3), 0.)
+        };
+        let phase = self.random.range(0., std::f32::consts::TAU);
+        self.particles.push(WeatherParticle { position, velocity, phase });
+    }
+
+    pub fn draw<T: RenderTarget>(&mut self, renderer: &mut Renderer<T>) -> Result<(), Error> {
+        self.view_size = renderer.view_size();
+        let settings = match self.kind.settings() {
+            Some(settings) => settings,
+            None => return Ok(()),
+        };
+        let (width, height) = self.view_size;
+
+        let previous_blend = renderer.blend_mode();
+        renderer.set_blend_mode(BlendMode::Blend);
+        renderer.set_draw_color(scale_alpha(settings.overlay, self.intensity));
+        renderer.fill_rect(Rect::new(0, 0, width, height))?;
+
+        renderer.set_draw_color(settings.color);
+        match self.kind {
+            WeatherKind::Rain | WeatherKind::Storm => {
+                for particle in &self.particles {
+                    let start = particle.position.truncate();
+                    let tail = (particle.velocity * 0.015).truncate();
+                    renderer.draw_line(start, start - tail)?;
+                }
+            }
+            WeatherKind::Snow => {
+                let flakes: Vec<Rect> = self.particles.iter()
+                    .map(|particle| {
+                        let sway = (self.ticks as f32 / 600. + particle.phase).sin() * 6.;
+                        let size = 2 + (particle.phase * 10.) as u32 % 3;
+                        Rect::new((particle.position.x + sway) as i32, particle.position.y as i32, size, size)
+                    })
+                    .collect();
+                renderer.fill_rects(&flakes)?;
+            }
+            WeatherKind::Sandstorm => {
+                let grains: Vec<Rect> = self.particles.iter()
+                    .map(|particle| Rect::new(particle.position.x as i32, particle.position.y as i32, 3, 1))
+                    .collect();
+                renderer.fill_rects(&grains)?;
+            }
+            WeatherKind::Clear => {}
+        }
+
+        if self.flash_ticks_left > 0 {
+            let fade = self.flash_ticks_left as f32 / FLASH_DURATION as f32;
+            renderer.set_draw_color(Color::RGBA(255, 255, 255, (200. * fade) as u8));
+            renderer.fill_rect(Rect::new(0, 0, width, height))?;
+        }
+        renderer.set_blend_mode(previous_blend);
+        Ok(())
+    }
+}
+
+impl Ticker for Weather {
+    fn advance(&mut self, ticks: u32) {
+        self.ticks = self.ticks.wrapping_add(ticks);
+        self.flash_ticks_left = self.flash_ticks_left.saturating_sub(ticks);
+        let settings = match self.kind.settings() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        let seconds = ticks as f32 / 1000.;
+        let (width, height) = (self.view_size.0 as f32, self.view_size.1 as f32);
+        let margin = 20.;
+        self.particles.retain_mut(|particle| {
+            particle.position += particle.velocity * seconds;
+            particle.position.x > -margin && particle.position.x < width * 1.3 + margin
+                && particle.position.y > -margin && particle.position.y < height + margin
+        });
+
+        let target = (settings.max_particles as f32 * self.intensity) as usize;
+        // Fill the screen at once when the weather starts, afterwards particles come in from the edges
+        let anywhere = self.particles.is_empty();
+        while self.particles.len() < target {
+            self.spawn(settings, anywhere);
+        }
+        self.particles.truncate(target);
+
+        if self.kind == WeatherKind::Storm && self.ticks.wrapping_sub(self.next_flash) < u32::MAX / 2 {
+            self.flash();
+            self.schedule_flash();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.particles.clear();
+        self.flash_ticks_left = 0;
+    }
+}
@@ -0,0 +1,118 @@
+use std::rc::Rc;
+
+use sdl2::rect::Rect;
+use sdl2::render::RenderTarget;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::texture::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FillMode {
+    Stretch,
+    Tile,
+}
+
+fn fill<T: RenderTarget>(renderer: &mut Renderer<T>, texture: &Texture, src: Rect, dst: Rect, mode: FillMode) -> Result<(), Error> {
+    match mode {
+        FillMode::Stretch => renderer.copy(texture, src, dst),
+        FillMode::Tile => {
+            let mut y = 0;
+            while y < dst.height() {
+                let height = src.height().min(dst.height() - y);
+                let mut x = 0;
+                while x < dst.width() {
+                    let width = src.width().min(dst.width() - x);
+                    renderer.copy(
+                        texture,
+                        Rect::new(src.x(), src.y(), width, height),
+                        Rect::new(dst.x() + x as i32, dst.y() + y as i32, width, height),
+                    )?;
+                    x += width;
+                }
+                y += height;
+            }
+            Ok(())
+        }
+    }
+}
+
+// A texture region split in 3x3 cells: corners are copied as-is, edges and center are filled to size
+pub struct NineSlice<'tx> {
+    texture: Rc<Texture<'tx>>,
+    rect: Rect,
+    border: u32,
+}
+
+impl<'tx> NineSlice<'tx> {
+    pub fn new(texture: Rc<Texture<'tx>>, rect: Rect, border: u32) -> Self {
+        NineSlice { texture, rect, border }
+    }
+
+    pub fn draw<T: RenderTarget>(&self, renderer: &mut Renderer<T>, dst: Rect, edges: FillMode, center: Option<FillMode>) -> Result<(), Error> {
+        let border = self.border.min(self.rect.width() / 2).min(self.rect.height() / 2)
+            .min(dst.width() / 2).min(dst.height() / 2);
+        let src_columns = slices(self.rect.x(), self.rect.width(), border);
+        let src_rows = slices(self.rect.y(), self.rect.height(), border);
+        let dst_columns = slices(dst.x(), dst.width(), border);
+        let dst_rows = slices(dst.y(), dst.height(), border);
+
+        for row in 0..3 {
+            for column in 0..3 {
+                // Rect clamps empty sizes to 1, so empty slices have to be skipped beforehand
+                if src_columns[column].1 == 0 || src_rows[row].1 == 0 || dst_columns[column].1 == 0 || dst_rows[row].1 == 0 {
+                    continue;
+                }
+                let src = Rect::new(src_columns[column].0, src_rows[row].0, src_columns[column].1, src_rows[row].1);
+                let cell = Rect::new(dst_columns[column].0, dst_rows[row].0, dst_columns[column].1, dst_rows[row].1);
+                let mode = match (row, column) {
+                    (1, 1) => match center {
+                        Some(mode) => mode,
+                        None => continue,
+                    },
+                    (1, _) | (_, 1) => edges,
+                    _ => FillMode::Stretch,
+                };
+                fill(renderer, &self.texture, src, cell, mode)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// (start, length) of the three slices of a span with the given border
+fn slices(start: i32, length: u32, border: u32) -> [(i32, u32); 3] {
+    let middle = length - 2 * border;
+    [
+        (start, border),
+        (start + border as i32, middle),
+        (start + (border + middle) as i32, border),
+    ]
+}
+
+pub struct WindowSkin<'tx> {
+    texture: Rc<Texture<'tx>>,
+    background: Rect,
+    background_fill: FillMode,
+    background_inset: u32,
+    frame: NineSlice<'tx>,
+    cursor: NineSlice<'tx>,
+}
+
+impl<'tx> WindowSkin<'tx> {
+    pub fn new(texture: Rc<Texture<'tx>>, background: Rect, background_fill: FillMode, background_inset: u32, frame: NineSlice<'tx>, cursor: NineSlice<'tx>) -> Self {
+        WindowSkin { texture, background, background_fill, background_inset, frame, cursor }
+    }
+
+    pub fn draw_window<T: RenderTarget>(&self, renderer: &mut Renderer<T>, dst: Rect) -> Result<(), Error> {
+        let inset = self.background_inset.min(dst.width() / 2).min(dst.height() / 2);
+        let background_dst = Rect::new(dst.x() + inset as i32, dst.y() + inset as i32, dst.width() - 2 * inset, dst.height() - 2 * inset);
+        fill(renderer, &self.texture, self.background, background_dst, self.background_fill)?;
+        self.frame.draw(renderer, dst, FillMode::Tile, None)
+    }
+
+    pub fn draw_cursor<T: RenderTarget>(&self, renderer: &mut Renderer<T>, dst: Rect) -> Result<(), Error> {
+        self.cursor.draw(renderer, dst, FillMode::Stretch, Some(FillMode::Stretch))
+    }
+}
@@ -0,0 +1,224 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::EventPump;
+use sdl2::image::LoadSurface;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::surface::Surface;
+use sdl2::ttf::Sdl2TtfContext;
+
+use crate::{Error, GameConfig, Resources, Scene};
+use crate::event::{GameState, PumpProcessor};
+use crate::gfx::capture::{FrameCapture, save_png};
+use crate::gfx::renderer::Renderer;
+use crate::gfx::texture::TextureLoader;
+use crate::gamepad::GamepadMap;
+use crate::keymap::KeyMap;
+use crate::resources::CachedResources;
+use crate::scene::main_menu::MainMenu;
+use crate::scene::map::MapScene;
+use crate::scene::stack::SceneStack;
+use crate::replay::Recording;
+use crate::settings::Settings;
+
+// Renders scenes into a software canvas and compares them against reference images.
+// Works without a display when SDL_VIDEODRIVER=dummy is set.
+
+const WIDTH: u32 = 1024;
+const HEIGHT: u32 = 768;
+const TICKS_PER_STEP: u32 = 16;
+
+type SceneBuilder = for<'ttf> fn(&GameConfig, &mut dyn Resources<'ttf>) -> Result<Box<dyn Scene<'ttf, Surface<'static>> + 'ttf>, Error>;
+
+pub struct GoldenCase {
+    pub name: &'static str,
+    pub steps: u32,
+    pub build: SceneBuilder,
+    // Recording in the reference directory to feed instead of live input. Its batches replace
+    // the first steps, ticks included, so steps should be at least as long as the recording.
+    pub replay: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    // Maximum difference allowed in any channel for a pixel to be considered equal
+    pub channel: u8,
+    // Number of different pixels allowed before the image is rejected
+    pub pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance { channel: 2, pixels: 0 }
+    }
+}
+
+pub fn cases() -> Vec<GoldenCase> {
+    vec![
+        GoldenCase {
+            name: "main_menu",
+            steps: 1,
+            build: |config, resources| {
+                let font = config.font.load(resources)?;
+                let window_skin = config.window_skin.as_ref().map(|skin| skin.load(resources)).transpose()?;
+                Ok(Box::new(MainMenu::new(font, window_skin, config.map.clone())))
+            },
+            replay: None,
+        },
+        GoldenCase {
+            name: "map",
+            steps: 60,
+            build: |config, resources| Ok(Box::new(MapScene::load(&config.map, resources)?)),
+            replay: None,
+        },
+    ]
+}
+
+pub struct GoldenRunner<'a> {
+    pub config: &'a GameConfig,
+    pub ttf: &'a Sdl2TtfContext,
+    pub reference_directory: PathBuf,
+    pub output_directory: PathBuf,
+    pub tolerance: Tolerance,
+    pub update: bool,
+}
+
+impl<'a> GoldenRunner<'a> {
+    // References in <data_path>/golden, failed images written to target/golden
+    pub fn new(config: &'a GameConfig, ttf: &'a Sdl2TtfContext, data_path: &Path, update: bool) -> Self {
+        GoldenRunner {
+            config,
+            ttf,
+            reference_directory: data_path.join("golden"),
+            output_directory: PathBuf::from("target/golden"),
+            tolerance: Tolerance::default(),
+            update,
+        }
+    }
+
+    // Returns whether all cases matched their references
+    pub fn run(&self, pump: EventPump, cases: &[GoldenCase]) -> Result<bool, Error> {
+        let mut pump_processor = PumpProcessor::new(pump, None);
+        let mut passed = true;
+        for case in cases {
+            let actual = self.render(case, &mut pump_processor)?;
+            let reference_path = self.reference_directory.join(format!("{}.png", case.name));
+            if self.update {
+                save_png(&actual, &reference_path)?;
+                println!("[golden] {}: reference updated", case.name);
+                continue;
+            }
+            let ok = self.check(case.name, &actual, &reference_path)?;
+            println!("[golden] {}: {}", case.name, if ok { "ok" } else { "FAILED" });
+            passed &= ok;
+        }
+        Ok(passed)
+    }
+
+    fn render(&self, case: &GoldenCase, pump_processor: &mut PumpProcessor) -> Result<Surface<'static>, Error> {
+        let mut canvas = Surface::new(WIDTH, HEIGHT, PixelFormatEnum::RGBA32)?.into_canvas()?;
+        let creator = canvas.texture_creator();
+        let resources = Box::new(CachedResources::new(TextureLoader::new(&creator), self.ttf));
+        let mut state = GameState::new(resources, FrameCapture::new(&self.output_directory, 1), Settings::new(KeyMap::default(), GamepadMap::default(), None));
+        // Run on a stack like in the game, so the scene gets its lifecycle calls and input contexts
+        let mut scene_stack = SceneStack::new(Vec::new());
+        let scene = (case.build)(self.config, state.resources.as_mut())?;
+        scene_stack.push(&mut state, scene, false, false);
+        if let Some(replay) = case.replay {
+            pump_processor.start_replay(Recording::load(self.reference_directory.join(replay))?);
+        }
+
+        for _ in 0..case.steps {
+            state.ticks_to_process = TICKS_PER_STEP;
+            scene_stack.frame(&mut state, pump_processor);
+        }
+        pump_processor.stop_replay();
+
+        let mut renderer = Renderer::new(&mut canvas);
+        renderer.set_draw_color(Color::BLACK);
+        renderer.clear();
+        scene_stack.draw(&mut renderer, state.resources.as_mut())?;
+        let surface = renderer.read_surface();
+        scene_stack.clear(&mut state);
+        surface
+    }
+
+    fn check(&self, name: &str, actual: &Surface, reference_path: &Path) -> Result<bool, Error> {
+        let reference = match Surface::from_file(reference_path) {
+            Ok(surface) => surface.convert_format(PixelFormatEnum::RGBA32)?,
+            Err(e) => {
+                println!("[golden] {}: could not load reference {:?} ({}). Run with --golden-update to create it", name, reference_path, e);
+                save_png(actual, self.output_directory.join(format!("{}.actual.png", name)))?;
+                return Ok(false);
+            }
+        };
+
+        if reference.size() != actual.size() {
+            println!("[golden] {}: size {:?} does not match reference size {:?}", name, actual.size(), reference.size());
+            save_png(actual, self.output_directory.join(format!("{}.actual.png", name)))?;
+            return Ok(false);
+        }
+
+        let (diff, mismatches) = diff_surfaces(actual, &reference, self.tolerance.channel)?;
+        if mismatches <= self.tolerance.pixels {
+            return Ok(true);
+        }
+        println!("[golden] {}: {} pixels differ (tolerance {})", name, mismatches, self.tolerance.pixels);
+        save_png(actual, self.output_directory.join(format!("{}.actual.png", name)))?;
+        save_png(&diff, self.output_directory.join(format!("{}.diff.png", name)))?;
+        Ok(false)
+    }
+}
+
+// Builds an image with mismatched pixels in red over a dimmed copy of the reference
+fn diff_surfaces(actual: &Surface, reference: &Surface, channel_tolerance: u8) -> Result<(Surface<'static>, usize), Error> {
+    let (width, height) = actual.size();
+    let mut diff = Surface::new(width, height, PixelFormatEnum::RGBA32)?;
+    let diff_pitch = diff.pitch() as usize;
+    let actual_pitch = actual.pitch() as usize;
+    let reference_pitch = reference.pitch() as usize;
+    let actual_pixels = actual.without_lock().ok_or_else(|| Error::simple("Actual surface must not need locking"))?;
+    let reference_pixels = reference.without_lock().ok_or_else(|| Error::simple("Reference surface must not need locking"))?;
+
+    let mut mismatches = 0;
+    diff.with_lock_mut(|diff_pixels| {
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let a = &actual_pixels[y * actual_pitch + x * 4..][..4];
+                let r = &reference_pixels[y * reference_pitch + x * 4..][..4];
+                let out = &mut diff_pixels[y * diff_pitch + x * 4..][..4];
+                if a.iter().zip(r).any(|(a, r)| a.abs_diff(*r) > channel_tolerance) {
+                    mismatches += 1;
+                    out.copy_from_slice(&[255, 0, 0, 255]);
+                } else {
+                    let gray = ((r[0] as u32 + r[1] as u32 + r[2] as u32) / 12) as u8;
+                    out.copy_from_slice(&[gray, gray, gray, 255]);
+                }
+            }
+        }
+    });
+    Ok((diff, mismatches))
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::image::InitFlag;
+
+    use crate::data::{self, Data};
+
+    use super::*;
+
+    // Compares against the references in data/golden, rewrite them with cargo run -- --golden-update
+    // when a change to the drawing is intended. Failed images go to target/golden.
+    #[test]
+    fn golden_images() {
+        let sdl2 = crate::test_sdl::init();
+        let _image = sdl2::image::init(InitFlag::PNG).unwrap();
+        let ttf = sdl2::ttf::init().unwrap();
+        let data_path = Path::new("data");
+        let mut config: GameConfig = data::load_file(data_path.join("config.json")).unwrap();
+        config.reroot(data_path);
+
+        let runner = GoldenRunner::new(&config, &ttf, data_path, false);
+        assert!(runner.run(sdl2.sdl.event_pump().unwrap(), &cases()).unwrap(), "Golden images differ, see target/golden");
+    }
+}
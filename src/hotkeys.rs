@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use sdl2::event::Event as SdlEvent;
+use sdl2::keyboard::Scancode;
+use sdl2::pixels::Color;
+use sdl2::render::RenderTarget;
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Event, EventListener, EventResult, GameState, InputState, Resources};
+use crate::game_event::GameEvent;
+use crate::gfx::font::TextFont;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::weather::WeatherKind;
+use crate::point::IntPoint;
+
+// Game speeds cycled through by Hotkey::Speed
+const SPEEDS: [f32; 4] = [1., 2., 4., 0.5];
+// Weathers cycled through by Hotkey::Weather, at full intensity
+const WEATHERS: [WeatherKind; 5] = [WeatherKind::Clear, WeatherKind::Rain, WeatherKind::Storm, WeatherKind::Snow, WeatherKind::Sandstorm];
+// How long the quit hotkey waits for its confirmation, and how long messages stay on screen
+const QUIT_CONFIRM_TIME: u32 = 2000;
+const MESSAGE_TIME: u32 = 2000;
+
+// Keys that work in every scene, handled by HotkeyListener before the scenes see them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Hotkey {
+    Quit,
+    Fullscreen,
+    Screenshot,
+    CaptureSequence,
+    DebugOverlay,
+    Mute,
+    Speed,
+    Weather,
+}
+
+impl Hotkey {
+    pub const ALL: [Hotkey; 8] = [Hotkey::Quit, Hotkey::Fullscreen, Hotkey::Screenshot, Hotkey::CaptureSequence, Hotkey::DebugOverlay, Hotkey::Mute, Hotkey::Speed, Hotkey::Weather];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hotkey::Quit => "Quit",
+            Hotkey::Fullscreen => "Fullscreen",
+            Hotkey::Screenshot => "Screenshot",
+            Hotkey::CaptureSequence => "Capture sequence",
+            Hotkey::DebugOverlay => "Debug overlay",
+            Hotkey::Mute => "Mute",
+            Hotkey::Speed => "Game speed",
+            Hotkey::Weather => "Weather",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HotkeyMap {
+    key_to_hotkey: HashMap<Scancode, Hotkey>,
+}
+
+impl HotkeyMap {
+    pub fn new() -> Self {
+        HotkeyMap { key_to_hotkey: HashMap::new() }
+    }
+
+    pub fn get_hotkey(&self, key: &Scancode) -> Option<Hotkey> {
+        self.key_to_hotkey.get(key).copied()
+    }
+
+    pub fn get_keys(&self, hotkey: Hotkey) -> Vec<Scancode> {
+        let mut keys: Vec<Scancode> = self.key_to_hotkey.iter()
+            .filter(|(_, bound)| **bound == hotkey)
+            .map(|(key, _)| *key)
+            .collect();
+        keys.sort_by_key(|key| *key as i32);
+        keys
+    }
+
+    // Binds the key to the hotkey, taking it away from any other hotkey
+    pub fn bind(&mut self, hotkey: Hotkey, key: Scancode) {
+        self.key_to_hotkey.insert(key, hotkey);
+    }
+
+    pub fn unbind(&mut self, hotkey: Hotkey) {
+        self.key_to_hotkey.retain(|_, bound| *bound != hotkey);
+    }
+}
+
+impl Default for HotkeyMap {
+    fn default() -> Self {
+        hardcoded_hotkey_map()
+    }
+}
+
+pub fn hardcoded_hotkey_map() -> HotkeyMap {
+    let scancode_map = [
+        (Scancode::Q, Hotkey::Quit),
+        (Scancode::F4, Hotkey::Fullscreen),
+        (Scancode::F12, Hotkey::Screenshot),
+        (Scancode::F11, Hotkey::CaptureSequence),
+        (Scancode::F3, Hotkey::DebugOverlay),
+        (Scancode::F8, Hotkey::Mute),
+        (Scancode::F5, Hotkey::Speed),
+        (Scancode::F6, Hotkey::Weather),
+    ];
+
+    let mut hotkey_map = HotkeyMap::new();
+    for (key, hotkey) in scancode_map.iter() {
+        hotkey_map.bind(*hotkey, *key);
+    }
+    hotkey_map
+}
+
+// What the hotkeys toggled. The main loop applies the parts that need the window or the timer.
+pub struct HotkeyState {
+    pub fullscreen: bool,
+    pub debug_overlay: bool,
+    pub muted: bool,
+    // Multiplies the ticks scenes get each frame
+    pub speed: f32,
+    // Short feedback shown on top of the scenes, e.g. the quit confirmation
+    pub message: Option<String>,
+}
+
+impl Default for HotkeyState {
+    fn default() -> Self {
+        HotkeyState { fullscreen: false, debug_overlay: false, muted: false, speed: 1., message: None }
+    }
+}
+
+// Handles the hotkeys bound in the settings and keeps their keys from the scenes. They are ignored
+// while a scene takes typed text or raw keys, so typing a name or binding a key can not quit the
+// game. Closing the window always quits. Hotkeys win over actions bound to the same key, see ActionResolver.
+#[derive(Default)]
+pub struct HotkeyListener {
+    // Milliseconds left to confirm quitting
+    quit_confirm_left: u32,
+    message_left: u32,
+    // Index in WEATHERS of the last weather asked for
+    weather_index: usize,
+}
+
+impl HotkeyListener {
+    pub fn new() -> Self {
+        HotkeyListener { quit_confirm_left: 0, message_left: 0, weather_index: 0 }
+    }
+
+    fn show(&mut self, state: &mut GameState, message: String) {
+        println!("{}", message);
+        state.hotkeys.message = Some(message);
+        self.message_left = MESSAGE_TIME;
+    }
+
+    fn trigger(&mut self, state: &mut GameState, hotkey: Hotkey) {
+        match hotkey {
+            Hotkey::Quit if self.quit_confirm_left > 0 => state.running = false,
+            Hotkey::Quit => {
+                self.quit_confirm_left = QUIT_CONFIRM_TIME;
+                let keys = state.settings.hotkeys.get_keys(Hotkey::Quit);
+                let key = keys.first().map(|key| key.name()).unwrap_or("it");
+                self.show(state, format!("Press {} again to quit", key));
+            }
+            Hotkey::Fullscreen => state.hotkeys.fullscreen = !state.hotkeys.fullscreen,
+            Hotkey::Screenshot => state.capture.request_screenshot(),
+            Hotkey::CaptureSequence => state.capture.toggle_sequence(),
+            Hotkey::DebugOverlay => state.hotkeys.debug_overlay = !state.hotkeys.debug_overlay,
+            Hotkey::Mute => {
+                state.hotkeys.muted = !state.hotkeys.muted;
+                // Only once the mixer is open, the flag is kept for when it is
+                if sdl2::mixer::query_spec().is_ok() {
+                    let volume = if state.hotkeys.muted { 0 } else { sdl2::mixer::MAX_VOLUME };
+                    sdl2::mixer::Channel::all().set_volume(volume);
+                    sdl2::mixer::Music::set_volume(volume);
+                }
+                self.show(state, if state.hotkeys.muted { "Sound off".to_owned() } else { "Sound on".to_owned() });
+            }
+            Hotkey::Speed => {
+                let current = SPEEDS.iter().position(|speed| *speed == state.hotkeys.speed).unwrap_or(0);
+                state.hotkeys.speed = SPEEDS[(current + 1) % SPEEDS.len()];
+                self.show(state, format!("Speed x{}", state.hotkeys.speed));
+            }
+            // Only the map reacts, see MapScene
+            Hotkey::Weather => {
+                self.weather_index = (self.weather_index + 1) % WEATHERS.len();
+                let kind = WEATHERS[self.weather_index];
+                state.events.publish(GameEvent::WeatherChanged { kind, intensity: 1. });
+                self.show(state, format!("Weather: {:?}", kind));
+            }
+        }
+    }
+}
+
+impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for HotkeyListener {
+    fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
+        match event {
+            Event::Sdl(SdlEvent::Quit { .. }) => Some(EventResult::Quit),
+            // Scenes do not see the keys used as hotkeys
+            Event::Sdl(SdlEvent::KeyDown { scancode: Some(key), .. } | SdlEvent::KeyUp { scancode: Some(key), .. }) if !state.text_input && !state.raw_keys => {
+                let hotkey = state.settings.hotkeys.get_hotkey(key)?;
+                if let Event::Sdl(SdlEvent::KeyDown { repeat: false, .. }) = event {
+                    self.trigger(state, hotkey);
+                }
+                Some(EventResult::Consumed)
+            }
+            _ => None,
+        }
+    }
+
+    fn batch_end(&mut self, state: &mut GameState<'ttf>, _input: &InputState) -> Option<EventResult<'ttf, T>> {
+        self.quit_confirm_left = self.quit_confirm_left.saturating_sub(state.ticks_to_process);
+        if self.message_left > 0 {
+            self.message_left = self.message_left.saturating_sub(state.ticks_to_process);
+            if self.message_left == 0 {
+                state.hotkeys.message = None;
+            }
+        }
+        None
+    }
+}
+
+// Draws the hotkey message and, when enabled, the debug overlay on top of the scenes
+pub struct DebugOverlay<'ttf> {
+    font: Rc<dyn TextFont<'ttf> + 'ttf>,
+}
+
+impl<'ttf> DebugOverlay<'ttf> {
+    pub fn new(font: Rc<dyn TextFont<'ttf> + 'ttf>) -> Self {
+        DebugOverlay { font }
+    }
+
+    pub fn draw<T: RenderTarget>(&self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>, hotkeys: &HotkeyState, debug_lines: &[String]) -> Result<(), Error> {
+        let line_height = self.font.line_height() as i32;
+        let mut y = 8;
+        if hotkeys.debug_overlay {
+            for line in debug_lines {
+                self.font.render(line, Color::YELLOW, resources)?.draw(renderer, IntPoint::new(8, y))?;
+                y += line_height;
+            }
+        }
+        if let Some(message) = &hotkeys.message {
+            let text = self.font.render(message, Color::WHITE, resources)?;
+            let x = (renderer.view_size().0 as i32 - text.width() as i32) / 2;
+            text.draw(renderer, IntPoint::new(x, renderer.view_size().1 as i32 - line_height - 8))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use std::rc::Rc;
+
+use sdl2::keyboard::Scancode;
+
+use crate::hotkeys::HotkeyMap;
+use crate::keymap::{Action, KeyMap};
+
+// Bindings for one kind of scene, layered over the base key map. Scenes name the context they
+// want (Scene::input_context) and the scene stack keeps the active ones in GameState.
+#[derive(Debug, Clone)]
+pub struct InputContext {
+    pub name: String,
+    // Actions the context binds, in the order they are shown for rebinding
+    pub actions: Vec<Action>,
+    pub key_map: Rc<KeyMap>,
+    // Keys this context does not bind are not looked up in the contexts below it, nor in the base key map
+    pub exclusive: bool,
+}
+
+impl InputContext {
+    pub fn new(name: &str, actions: Vec<Action>, key_map: KeyMap, exclusive: bool) -> Self {
+        InputContext { name: name.to_owned(), actions, key_map: Rc::new(key_map), exclusive }
+    }
+}
+
+pub const MAP_CONTEXT: &str = "map";
+
+pub fn map_keymap() -> KeyMap {
+    let scancode_map = [
+        (Scancode::LShift, Action::Run),
+        (Scancode::RShift, Action::Run),
+        (Scancode::Space, Action::Interact),
+        (Scancode::Return, Action::Interact),
+        (Scancode::Escape, Action::Menu),
+        (Scancode::M, Action::ToggleMinimap),
+    ];
+
+    let mut key_map = KeyMap::new(Default::default());
+    for (key, action) in scancode_map.iter() {
+        key_map.bind(*action, *key);
+    }
+    key_map
+}
+
+// Every context the game knows, with its default bindings
+pub fn default_contexts() -> Vec<InputContext> {
+    vec![
+        InputContext::new(MAP_CONTEXT, vec![Action::Run, Action::Interact, Action::Menu, Action::ToggleMinimap], map_keymap(), false),
+    ]
+}
+
+// Looks keys up through the active contexts, top first, then the base key map. Keys bound to a
+// hotkey resolve to no action, so they reach HotkeyListener as keys whatever else they are bound to.
+pub struct ActionResolver<'a> {
+    base: &'a KeyMap,
+    // Topmost first
+    contexts: Vec<&'a InputContext>,
+    hotkeys: &'a HotkeyMap,
+}
+
+impl<'a> ActionResolver<'a> {
+    pub fn new(base: &'a KeyMap, contexts: Vec<&'a InputContext>, hotkeys: &'a HotkeyMap) -> Self {
+        ActionResolver { base, contexts, hotkeys }
+    }
+
+    pub fn get_action(&self, key: &Scancode) -> Option<Action> {
+        if self.hotkeys.get_hotkey(key).is_some() {
+            return None;
+        }
+        for context in &self.contexts {
+            if let Some(action) = context.key_map.get_action(key) {
+                return Some(*action);
+            }
+            if context.exclusive {
+                return None;
+            }
+        }
+        self.base.get_action(key).copied()
+    }
+
+    // Keys that currently resolve to the action, so keys taken over by a context do not count for the base action
+    pub fn get_keys(&self, action: Action) -> Vec<Scancode> {
+        let mut keys: Vec<Scancode> = Vec::new();
+        for key_map in self.contexts.iter().map(|context| context.key_map.as_ref()).chain(std::iter::once(self.base)) {
+            for key in key_map.get_keys(&action).into_iter().flatten() {
+                if !keys.contains(key) && self.get_action(key) == Some(action) {
+                    keys.push(*key);
+                }
+            }
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hotkeys::{Hotkey, HotkeyMap};
+
+    use super::*;
+
+    #[test]
+    fn hotkeys_win_over_actions() {
+        let mut base = KeyMap::default();
+        base.bind(Action::Select, Scancode::Q);
+        let mut hotkeys = HotkeyMap::new();
+        let resolver = ActionResolver::new(&base, Vec::new(), &hotkeys);
+        assert_eq!(resolver.get_action(&Scancode::Q), Some(Action::Select));
+
+        hotkeys.bind(Hotkey::Quit, Scancode::Q);
+        let resolver = ActionResolver::new(&base, Vec::new(), &hotkeys);
+        assert_eq!(resolver.get_action(&Scancode::Q), None);
+        assert!(!resolver.get_keys(Action::Select).contains(&Scancode::Q));
+        assert_eq!(resolver.get_action(&Scancode::Return), Some(Action::Select));
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use sdl2::render::RenderTarget;
+
+use crate::event::{Event, EventListener, GameState, InputState, PumpProcessor};
+use crate::keymap::Action;
+
+// Where a frame's input comes from: the SDL event pump when playing, a script when testing scenes
+pub trait InputSource {
+    // Delivers one batch of input to the listener: batch_start, the events, then batch_end
+    fn process_events<'ttf, T: RenderTarget, L: EventListener<'ttf, T> + ?Sized>(&mut self, state: &mut GameState<'ttf>, listener: &mut L);
+
+    // Input state at the end of the last batch, what Scene::update sees
+    fn input(&self) -> &InputState;
+}
+
+impl InputSource for PumpProcessor {
+    fn process_events<'ttf, T: RenderTarget, L: EventListener<'ttf, T> + ?Sized>(&mut self, state: &mut GameState<'ttf>, listener: &mut L) {
+        PumpProcessor::process_events(self, state, listener)
+    }
+
+    fn input(&self) -> &InputState {
+        PumpProcessor::input(self)
+    }
+}
+
+// One batch of scripted input: the events, and the actions held once they are handled
+pub struct ScriptedBatch {
+    pub events: Vec<Event>,
+    pub pressed: Vec<Action>,
+}
+
+// Input written by hand, so scenes can be driven without SDL. Every call to process_events takes
+// the next batch, its start state being the previous batch's end. Once the script is over batches
+// are empty and keep the last pressed actions. Ticks are left to the caller (state.ticks_to_process).
+//
+//     let mut input = ScriptedInput::new();
+//     input.tap(Action::Down);
+//     input.tap(Action::Select);
+//     while !input.is_finished() {
+//         scene_stack.frame(&mut state, &mut input);
+//     }
+#[derive(Default)]
+pub struct ScriptedInput {
+    batches: VecDeque<ScriptedBatch>,
+    input: InputState,
+}
+
+impl ScriptedInput {
+    pub fn new() -> Self {
+        ScriptedInput { batches: VecDeque::new(), input: InputState::default() }
+    }
+
+    pub fn batch(&mut self, events: Vec<Event>, pressed: &[Action]) -> &mut Self {
+        self.batches.push_back(ScriptedBatch { events, pressed: pressed.to_vec() });
+        self
+    }
+
+    // A batch without events, e.g. to let time pass
+    pub fn wait(&mut self) -> &mut Self {
+        let pressed = self.last_pressed();
+        self.batch(Vec::new(), &pressed)
+    }
+
+    // Presses the action in one batch and releases it in the next
+    pub fn tap(&mut self, action: Action) -> &mut Self {
+        self.press(action).release(action)
+    }
+
+    pub fn press(&mut self, action: Action) -> &mut Self {
+        let mut pressed = self.last_pressed();
+        if !pressed.contains(&action) {
+            pressed.push(action);
+        }
+        self.batch(vec![Event::ActionDown { action }], &pressed)
+    }
+
+    pub fn release(&mut self, action: Action) -> &mut Self {
+        let mut pressed = self.last_pressed();
+        pressed.retain(|held| *held != action);
+        self.batch(vec![Event::ActionUp { action }], &pressed)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.batches.is_empty()
+    }
+
+    // Actions held at the end of the script so far
+    fn last_pressed(&self) -> Vec<Action> {
+        match self.batches.back() {
+            Some(batch) => batch.pressed.clone(),
+            None => self.input.pressed().to_vec(),
+        }
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn process_events<'ttf, T: RenderTarget, L: EventListener<'ttf, T> + ?Sized>(&mut self, state: &mut GameState<'ttf>, listener: &mut L) {
+        let start = self.input.clone();
+        let batch = self.batches.pop_front().unwrap_or_else(|| ScriptedBatch { events: Vec::new(), pressed: start.pressed().to_vec() });
+        listener.batch_start(state, &start);
+        for event in &batch.events {
+            listener.process_event(state, event);
+        }
+        self.input = InputState::new(batch.pressed);
+        listener.batch_end(state, &self.input);
+    }
+
+    fn input(&self) -> &InputState {
+        &self.input
+    }
+}
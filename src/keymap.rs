@@ -1,8 +1,12 @@
 use std::collections::HashMap;
 
-use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::keyboard::Scancode;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+// An action can have a primary and a secondary key, binding a third one drops the oldest
+pub const MAX_KEYS_PER_ACTION: usize = 2;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub enum Action {
     Select,
     Back,
@@ -10,8 +14,34 @@ pub enum Action {
     Down,
     Left,
     Right,
+    Run,
+    Interact,
+    Menu,
+    ToggleMinimap,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [Action::Up, Action::Down, Action::Left, Action::Right, Action::Select, Action::Back, Action::Run, Action::Interact, Action::Menu, Action::ToggleMinimap];
+    // Actions bound by the base key map, that every scene gets unless a context overrides the key
+    pub const BASE: [Action; 6] = [Action::Up, Action::Down, Action::Left, Action::Right, Action::Select, Action::Back];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Select => "Select",
+            Action::Back => "Back",
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Run => "Run",
+            Action::Interact => "Interact",
+            Action::Menu => "Menu",
+            Action::ToggleMinimap => "Minimap",
+        }
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct KeyMap {
     key_to_action: HashMap<Scancode, Action>,
     action_to_keys: HashMap<Action, Vec<Scancode>>,
@@ -41,32 +71,65 @@ impl KeyMap {
     pub fn get_keys(&self, action: &Action) -> Option<&Vec<Scancode>> {
         self.action_to_keys.get(action)
     }
+
+    // Binds the key to the action, taking it away from any other action
+    pub fn bind(&mut self, action: Action, key: Scancode) {
+        if let Some(previous) = self.key_to_action.insert(key, action) {
+            if let Some(keys) = self.action_to_keys.get_mut(&previous) {
+                keys.retain(|k| *k != key);
+            }
+        }
+        let keys = self.action_to_keys.entry(action).or_default();
+        keys.push(key);
+        while keys.len() > MAX_KEYS_PER_ACTION {
+            let removed = keys.remove(0);
+            self.key_to_action.remove(&removed);
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action) {
+        if let Some(keys) = self.action_to_keys.remove(&action) {
+            for key in keys {
+                self.key_to_action.remove(&key);
+            }
+        }
+    }
+
+    pub fn unbind_key(&mut self, key: Scancode) {
+        if let Some(action) = self.key_to_action.remove(&key) {
+            if let Some(keys) = self.action_to_keys.get_mut(&action) {
+                keys.retain(|k| *k != key);
+            }
+        }
+    }
 }
 
+impl Default for KeyMap {
+    fn default() -> Self {
+        hardcoded_keymap()
+    }
+}
+
+// Defaults used when there are no saved settings, and by reset to defaults
 pub fn hardcoded_keymap() -> KeyMap {
-    let keycode_map = [
-        (Keycode::Up, Action::Up),
-        (Keycode::Down, Action::Down),
-        (Keycode::Left, Action::Left),
-        (Keycode::Right, Action::Right),
-        (Keycode::W, Action::Up),
-        (Keycode::S, Action::Down),
-        (Keycode::A, Action::Left),
-        (Keycode::D, Action::Right),
-        (Keycode::Return, Action::Select),
-        (Keycode::Escape, Action::Back),
+    // Scancodes rather than keycodes, they follow key positions whatever the layout and need no SDL to convert
+    let scancode_map = [
+        (Scancode::Up, Action::Up),
+        (Scancode::Down, Action::Down),
+        (Scancode::Left, Action::Left),
+        (Scancode::Right, Action::Right),
+        (Scancode::W, Action::Up),
+        (Scancode::S, Action::Down),
+        (Scancode::A, Action::Left),
+        (Scancode::D, Action::Right),
+        (Scancode::Return, Action::Select),
+        (Scancode::Escape, Action::Back),
     ];
 
-    KeyMap::new(keycode_map.iter()
-        .map(|(key, action)| (Scancode::from_keycode(*key).unwrap(), *action))
-        .collect())
+    // Bound one by one so the keys keep this order
+    let mut key_map = KeyMap::new(HashMap::new());
+    for (key, action) in scancode_map.iter() {
+        key_map.bind(*action, *key);
+    }
+    key_map
 }
-
-// let KEY_MAP: HashMap<Keycode, Action> = HashMap::from([
-//     (Keycode::Up, Action::Up),
-//     (Keycode::Down, Action::Down),
-//     (Keycode::Left, Action::Left),
-//     (Keycode::Right, Action::Right),
-//     (Keycode::Return, Action::Select),
-//     (Keycode::Escape, Action::Back),
-// ]);
\ No newline at end of file
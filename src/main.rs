@@ -1,7 +1,6 @@
 use std::path::Path;
 
 use sdl2::pixels::Color;
-use sdl2::render::RenderTarget;
 use sdl2::video::Window;
 
 use gfx::texture::TextureLoader;
@@ -9,23 +8,41 @@ use gfx::texture::TextureLoader;
 use crate::data::{Data, GameConfig};
 use crate::data::map::{MapData};
 use crate::error::Error;
-use crate::event::{EventListener, EventResult, GameState, PumpProcessor, QuitListener, InputState, Event};
-use crate::gfx::renderer::{BackBuffer, Renderer};
+use crate::event::{EventListener, EventResult, GameState, PumpProcessor, InputState, Event};
+use crate::gfx::renderer::BackBuffer;
 use crate::gfx::spritesheet::SpriteSheet;
-use crate::keymap::hardcoded_keymap;
+use crate::game_event::EventLog;
+use crate::gamepad::Gamepads;
+use crate::golden::GoldenRunner;
+use crate::hotkeys::{DebugOverlay, HotkeyListener};
 use crate::point::Point;
+use crate::replay::Recording;
 use crate::resources::{CachedResources, Resources};
 use crate::scene::{main_menu::MainMenu, Scene};
+use crate::scene::stack::SceneStack;
+use crate::settings::Settings;
 
 pub mod data;
 pub mod direction;
 pub mod error;
 pub mod event;
+pub mod game_event;
+pub mod gamepad;
 pub mod gfx;
+pub mod golden;
+pub mod hotkeys;
+pub mod input_context;
+pub mod input_source;
 pub mod keymap;
 pub mod point;
+pub mod random;
+pub mod repeat;
+pub mod replay;
 pub mod resources;
 pub mod scene;
+pub mod settings;
+#[cfg(test)]
+mod test_sdl;
 pub mod utils;
 
 fn main() {
@@ -35,11 +52,6 @@ fn main() {
 fn run() -> Result<(), Error> {
     let sdl2 = sdl2::init()?;
     let timer = sdl2.timer()?;
-    let video = sdl2.video()?;
-    let window = video
-        .window("The rpg", 1024, 768)
-        .build()
-        .map_err(|e| e.to_string())?;
     let _image = sdl2::image::init(sdl2::image::InitFlag::PNG)?;
     let ttf = sdl2::ttf::init().map_err(|e| e.to_string())?;
 
@@ -51,48 +63,106 @@ fn run() -> Result<(), Error> {
 
     config.reroot(data_path);
     let pump = sdl2.event_pump()?;
+
+    // Golden image checks: SDL_VIDEODRIVER=dummy cargo run -- --golden (or --golden-update to rewrite references)
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--golden" || arg == "--golden-update") {
+        let runner = GoldenRunner::new(&config, &ttf, data_path, args.iter().any(|arg| arg == "--golden-update"));
+        if !runner.run(pump, &golden::cases())? {
+            return Err(Error::simple("Golden image checks failed"));
+        }
+        return Ok(());
+    }
+
+    // The golden checks render offscreen, only the game needs a window
+    let video = sdl2.video()?;
+    let window = video
+        .window("The rpg", 1024, 768)
+        .build()
+        .map_err(|e| e.to_string())?;
     let canvas = window.into_canvas()
         .accelerated()
         // .present_vsync()
         .build()
         .map_err(|e| e.to_string())?;
 
+    let window_size = canvas.window().size();
+    let view_size = canvas.output_size()?;
     let creator = canvas.texture_creator();
     let loader = TextureLoader::new(&creator);
     let mut back_buffer = BackBuffer::new(canvas, &creator)?;
-    let resources = CachedResources::new(loader, &ttf);
-    let mut state = GameState::new(resources);
+    let resources = Box::new(CachedResources::new(loader, &ttf));
+    let mut state = GameState::new(resources, config.capture.to_frame_capture(), Settings::load(data_path.join("settings.json")));
 
     let mut listeners: Vec<Box<dyn EventListener<Window>>> = Vec::new();
-    listeners.push(Box::new(QuitListener {}));
-
-    let mut scene_stack: SceneStack<Window> = SceneStack {
-        global_listeners: listeners,
-        stack: Vec::new(),
-    };
-    let thebox = Box::new(MainMenu::new(config.font.load(&mut state.resources)?, config.map));
-    scene_stack.stack.push(thebox);
+    listeners.push(Box::new(HotkeyListener::new()));
+
+    let mut scene_stack: SceneStack<Window> = SceneStack::new(listeners);
+    state.events.subscribe(Box::new(EventLog {}));
+    let window_skin = config.window_skin.as_ref()
+        .map(|skin| skin.load(state.resources.as_mut()))
+        .transpose()?;
+    let font = config.font.load(state.resources.as_mut())?;
+    let debug_overlay = DebugOverlay::new(font.clone());
+    let thebox = Box::new(MainMenu::new(font, window_skin, config.map));
+    scene_stack.push(&mut state, thebox, false, false);
     let mut frame_count = 0;
     let mut last_frames = [0u32; 500];
     let mut last_ticks = timer.ticks();
-    let key_map = hardcoded_keymap();
-    let mut pump_processor = PumpProcessor::new(pump, key_map);
+    // Fraction of a tick left over when the game speed is not a whole number
+    let mut tick_remainder = 0f32;
+    let gamepads = match sdl2.game_controller() {
+        Ok(subsystem) => Some(Gamepads::new(subsystem)),
+        Err(e) => {
+            println!("Gamepads disabled: {}", e);
+            None
+        }
+    };
+    let mut pump_processor = PumpProcessor::new(pump, gamepads);
+    pump_processor.set_view_scale(window_size, view_size);
+    // --record <file> saves the session's input as it goes and when the game exits, --replay <file> plays it back
+    if let Some(path) = arg_value(&args, "--record") {
+        pump_processor.start_recording(path);
+    }
+    if let Some(path) = arg_value(&args, "--replay") {
+        pump_processor.start_replay(Recording::load(path)?);
+    }
     while state.running {
         let current_ticks = timer.ticks();
-        state.ticks_to_process = current_ticks - last_ticks;
+        let elapsed = current_ticks - last_ticks;
         if frame_count > 0 {
-            last_frames[frame_count % last_frames.len()] = state.ticks_to_process;
+            last_frames[frame_count % last_frames.len()] = elapsed;
         }
         last_ticks = current_ticks;
-
-
-        pump_processor.process_events(&mut state, &mut scene_stack);
+        let scaled_ticks = elapsed as f32 * state.hotkeys.speed + tick_remainder;
+        state.ticks_to_process = scaled_ticks as u32;
+        tick_remainder = scaled_ticks - state.ticks_to_process as f32;
+
+        scene_stack.frame(&mut state, &mut pump_processor);
+        if state.hotkeys.fullscreen != back_buffer.is_fullscreen() {
+            back_buffer.set_fullscreen(state.hotkeys.fullscreen)?;
+            pump_processor.set_view_scale(back_buffer.window_size(), back_buffer.view_size());
+        }
+        let debug_lines = if state.hotkeys.debug_overlay {
+            vec![
+                format!("Frame: {} ms, speed x{}", elapsed, state.hotkeys.speed),
+                format!("Scenes: {}", scene_stack.len()),
+                format!("Input contexts: {}", state.input_contexts.join(", ")),
+            ]
+        } else {
+            Vec::new()
+        };
         back_buffer.render_and_flip(|renderer| {
             renderer.set_draw_color(Color::BLACK);
             renderer.clear();
-            scene_stack.draw(renderer, &mut state.resources)?;
+            scene_stack.draw(renderer, state.resources.as_mut())?;
+            debug_overlay.draw(renderer, state.resources.as_mut(), &state.hotkeys, &debug_lines)?;
             Ok(())
         })?;
+        // Capturing is a debugging aid, failing to save a frame does not end the game
+        if let Err(e) = state.capture.capture_frame(&mut back_buffer) {
+            println!("Could not capture the frame: {:?}", e);
+        }
 
         frame_count += 1;
         if frame_count % last_frames.len() == 0 {
@@ -102,63 +172,14 @@ fn run() -> Result<(), Error> {
             println!("Last {} frames took {} ms. Biggest frame: {} ms. Avg FPS: {}", last_frames.len(), sum, max.unwrap_or(&0), fps);
         }
     }
+    scene_stack.clear(&mut state);
+    pump_processor.stop_recording()?;
 
     Ok(())
 }
 
-struct SceneStack<'ttf, T: RenderTarget> {
-    pub global_listeners: Vec<Box<dyn EventListener<'ttf, T>>>,
-    pub stack: Vec<Box<dyn Scene<'ttf, T> + 'ttf>>,
-}
-
-impl<'ttf, T: RenderTarget> SceneStack<'ttf, T> {
-    fn process(&mut self, result: Option<EventResult<'ttf, T>>) {
-        match result {
-            Some(EventResult::PushScene(scene)) => { self.stack.push(scene); }
-            Some(EventResult::PopScene) => { self.stack.pop(); }
-            _ => {}
-        };
-    }
-}
-
-impl<'ttf, T: RenderTarget> SceneStack<'ttf, T> {
-    fn active_scene_mut(&mut self) -> &mut dyn Scene<'ttf, T> {
-        self.stack.last_mut().unwrap().as_mut()
-    }
-}
-
-
-impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for SceneStack<'ttf, T> {
-    fn batch_start(&mut self, state: &mut GameState<'ttf>, input: &InputState) -> Option<EventResult<'ttf, T>> {
-        for listener in self.global_listeners.iter_mut() {
-            listener.batch_start(state, input);
-        }
-        let result = self.active_scene_mut().batch_start(state, input);
-        self.process(result);
-        None
-    }
-
-    fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
-        for listener in self.global_listeners.iter_mut() {
-            listener.process_event(state, event);
-        }
-        let result = self.active_scene_mut().process_event(state, event);
-        self.process(result);
-        None
-    }
-
-    fn batch_end(&mut self, state: &mut GameState<'ttf>, input: &InputState) -> Option<EventResult<'ttf, T>> {
-        for listener in self.global_listeners.iter_mut() {
-            listener.batch_end(state, input);
-        }
-        let result = self.active_scene_mut().batch_end(state, input);
-        self.process(result);
-        None
-    }
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter().position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.as_str())
 }
-
-impl<'ttf, T: RenderTarget> Scene<'ttf, T> for SceneStack<'ttf, T> {
-    fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error> {
-        self.active_scene_mut().draw(renderer, resources)
-    }
-}
\ No newline at end of file
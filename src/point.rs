@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::direction::Direction;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Point<T: Copy> {
     pub x: T,
     pub y: T,
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::event::{Event, InputState};
+use crate::keymap::Action;
+
+// Times in milliseconds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RepeatConfig {
+    // How long an action must be held before it starts repeating
+    pub delay: u32,
+    // Time between repeats afterwards
+    pub interval: u32,
+}
+
+impl Default for RepeatConfig {
+    fn default() -> Self {
+        RepeatConfig { delay: 400, interval: 80 }
+    }
+}
+
+// Turns held actions into Event::ActionRepeat, whatever device they come from. It only
+// sees actions, so keyboard repeat from the OS is dropped before it gets here.
+// Scenes opt in per action by matching ActionRepeat next to ActionDown.
+#[derive(Default)]
+pub struct KeyRepeat {
+    // Held actions with the milliseconds left until their next repeat
+    held: Vec<(Action, u32)>,
+}
+
+impl KeyRepeat {
+    pub fn new() -> Self {
+        KeyRepeat { held: Vec::new() }
+    }
+
+    pub fn track(&mut self, event: &Event, config: &RepeatConfig) {
+        match event {
+            Event::ActionDown { action } if !self.held.iter().any(|(held, _)| held == action) => self.held.push((*action, config.delay)),
+            Event::ActionUp { action } => self.held.retain(|(held, _)| held != action),
+            _ => {}
+        }
+    }
+
+    // Returns the repeats due after ticks more milliseconds. Actions that are no longer pressed
+    // are forgotten even if their release was missed, e.g. when the window lost focus.
+    pub fn advance(&mut self, ticks: u32, input: &InputState, config: &RepeatConfig) -> Vec<Event> {
+        self.held.retain(|(action, _)| input.is_action_pressed(*action));
+        let mut repeats = Vec::new();
+        for (action, left) in self.held.iter_mut() {
+            if *left > ticks {
+                *left -= ticks;
+            } else {
+                // At most one repeat per batch, so a slow frame does not scroll through a whole menu
+                repeats.push(Event::ActionRepeat { action: *action });
+                *left = config.interval.max(1);
+            }
+        }
+        repeats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: RepeatConfig = RepeatConfig { delay: 400, interval: 80 };
+
+    fn repeated(repeats: Vec<Event>) -> Vec<Action> {
+        repeats.iter().map(|event| match event {
+            Event::ActionRepeat { action } => *action,
+            other => panic!("Not a repeat: {:?}", other),
+        }).collect()
+    }
+
+    #[test]
+    fn delay_then_interval() {
+        let mut repeat = KeyRepeat::new();
+        let held = InputState::new(vec![Action::Down]);
+        repeat.track(&Event::ActionDown { action: Action::Down }, &CONFIG);
+        assert!(repeat.advance(399, &held, &CONFIG).is_empty());
+        assert_eq!(repeated(repeat.advance(1, &held, &CONFIG)), vec![Action::Down]);
+        assert!(repeat.advance(79, &held, &CONFIG).is_empty());
+        assert_eq!(repeated(repeat.advance(1, &held, &CONFIG)), vec![Action::Down]);
+        assert!(repeat.advance(40, &held, &CONFIG).is_empty());
+        assert_eq!(repeated(repeat.advance(40, &held, &CONFIG)), vec![Action::Down]);
+
+        // A slow frame gives a single repeat and restarts the interval
+        assert_eq!(repeated(repeat.advance(1000, &held, &CONFIG)), vec![Action::Down]);
+        assert!(repeat.advance(79, &held, &CONFIG).is_empty());
+
+        // Releasing stops it, pressing again waits for the whole delay
+        repeat.track(&Event::ActionUp { action: Action::Down }, &CONFIG);
+        assert!(repeat.advance(1000, &held, &CONFIG).is_empty());
+        repeat.track(&Event::ActionDown { action: Action::Down }, &CONFIG);
+        assert!(repeat.advance(399, &held, &CONFIG).is_empty());
+        assert_eq!(repeated(repeat.advance(1, &held, &CONFIG)), vec![Action::Down]);
+    }
+
+    #[test]
+    fn held_actions_repeat_on_their_own() {
+        let mut repeat = KeyRepeat::new();
+        let held = InputState::new(vec![Action::Down, Action::Left]);
+        repeat.track(&Event::ActionDown { action: Action::Down }, &CONFIG);
+        assert!(repeat.advance(200, &held, &CONFIG).is_empty());
+        repeat.track(&Event::ActionDown { action: Action::Left }, &CONFIG);
+        // Another down while held, e.g. from a second key, does not restart the delay
+        repeat.track(&Event::ActionDown { action: Action::Down }, &CONFIG);
+        assert_eq!(repeated(repeat.advance(200, &held, &CONFIG)), vec![Action::Down]);
+        assert!(repeat.advance(79, &held, &CONFIG).is_empty());
+        assert_eq!(repeated(repeat.advance(1, &held, &CONFIG)), vec![Action::Down]);
+        assert_eq!(repeated(repeat.advance(120, &held, &CONFIG)), vec![Action::Down, Action::Left]);
+    }
+
+    #[test]
+    fn missed_releases_are_forgotten() {
+        let mut repeat = KeyRepeat::new();
+        repeat.track(&Event::ActionDown { action: Action::Down }, &CONFIG);
+        assert!(repeat.advance(1000, &InputState::new(Vec::new()), &CONFIG).is_empty());
+        // Still forgotten once pressed again without a new down
+        assert!(repeat.advance(1000, &InputState::new(vec![Action::Down]), &CONFIG).is_empty());
+    }
+
+    #[test]
+    fn zero_interval_repeats_every_millisecond() {
+        let config = RepeatConfig { delay: 0, interval: 0 };
+        let mut repeat = KeyRepeat::new();
+        let held = InputState::new(vec![Action::Up]);
+        repeat.track(&Event::ActionDown { action: Action::Up }, &config);
+        assert_eq!(repeated(repeat.advance(0, &held, &config)), vec![Action::Up]);
+        // The interval is at least a millisecond, so batches without time give nothing
+        assert!(repeat.advance(0, &held, &config).is_empty());
+        for _ in 0..3 {
+            assert_eq!(repeated(repeat.advance(1, &held, &config)), vec![Action::Up]);
+        }
+    }
+}
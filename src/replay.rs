@@ -0,0 +1,282 @@
+use std::path::{Path, PathBuf};
+
+use sdl2::event::Event as SdlEvent;
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use sdl2::mouse::MouseButton;
+use sdl2::render::RenderTarget;
+use serde::{Deserialize, Serialize};
+
+use crate::data;
+use crate::error::Error;
+use crate::event::{Event, EventListener, GameState, InputState};
+use crate::input_source::InputSource;
+use crate::keymap::Action;
+use crate::point::IntPoint;
+
+// Serializable copy of an Event. Only the SDL events listeners react to are kept, the rest
+// (window, joystick...) are dropped when recording.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RecordedEvent {
+    ActionDown { action: Action },
+    ActionUp { action: Action },
+    ActionRepeat { action: Action },
+    Quit,
+    // Scancodes and keycodes are stored by their SDL names
+    KeyDown { scancode: Option<String>, keycode: Option<String>, repeat: bool },
+    KeyUp { scancode: Option<String>, keycode: Option<String>, repeat: bool },
+    TextInput { text: String },
+    // Buttons are stored as SDL button numbers
+    MouseMove { position: IntPoint },
+    MouseDown { button: u8, position: IntPoint },
+    MouseUp { button: u8, position: IntPoint },
+}
+
+impl RecordedEvent {
+    pub fn from_event(event: &Event) -> Option<Self> {
+        Some(match event {
+            Event::ActionDown { action } => RecordedEvent::ActionDown { action: *action },
+            Event::ActionUp { action } => RecordedEvent::ActionUp { action: *action },
+            Event::ActionRepeat { action } => RecordedEvent::ActionRepeat { action: *action },
+            Event::Sdl(SdlEvent::Quit { .. }) => RecordedEvent::Quit,
+            Event::Sdl(SdlEvent::KeyDown { scancode, keycode, repeat, .. }) => RecordedEvent::KeyDown {
+                scancode: scancode.map(|s| s.name().to_owned()),
+                keycode: keycode.map(|k| k.name()),
+                repeat: *repeat,
+            },
+            Event::Sdl(SdlEvent::KeyUp { scancode, keycode, repeat, .. }) => RecordedEvent::KeyUp {
+                scancode: scancode.map(|s| s.name().to_owned()),
+                keycode: keycode.map(|k| k.name()),
+                repeat: *repeat,
+            },
+            Event::Sdl(SdlEvent::TextInput { text, .. }) => RecordedEvent::TextInput { text: text.clone() },
+            Event::MouseMove { position } => RecordedEvent::MouseMove { position: *position },
+            Event::MouseDown { button, position } => RecordedEvent::MouseDown { button: *button as u8, position: *position },
+            Event::MouseUp { button, position } => RecordedEvent::MouseUp { button: *button as u8, position: *position },
+            _ => return None,
+        })
+    }
+
+    pub fn to_event(&self) -> Event {
+        match self {
+            RecordedEvent::ActionDown { action } => Event::ActionDown { action: *action },
+            RecordedEvent::ActionUp { action } => Event::ActionUp { action: *action },
+            RecordedEvent::ActionRepeat { action } => Event::ActionRepeat { action: *action },
+            RecordedEvent::Quit => Event::Sdl(SdlEvent::Quit { timestamp: 0 }),
+            RecordedEvent::KeyDown { scancode, keycode, repeat } => Event::Sdl(SdlEvent::KeyDown {
+                timestamp: 0,
+                window_id: 0,
+                keycode: keycode.as_deref().and_then(Keycode::from_name),
+                scancode: scancode.as_deref().and_then(Scancode::from_name),
+                keymod: Mod::NOMOD,
+                repeat: *repeat,
+            }),
+            RecordedEvent::KeyUp { scancode, keycode, repeat } => Event::Sdl(SdlEvent::KeyUp {
+                timestamp: 0,
+                window_id: 0,
+                keycode: keycode.as_deref().and_then(Keycode::from_name),
+                scancode: scancode.as_deref().and_then(Scancode::from_name),
+                keymod: Mod::NOMOD,
+                repeat: *repeat,
+            }),
+            RecordedEvent::TextInput { text } => Event::Sdl(SdlEvent::TextInput { timestamp: 0, window_id: 0, text: text.clone() }),
+            RecordedEvent::MouseMove { position } => Event::MouseMove { position: *position },
+            RecordedEvent::MouseDown { button, position } => Event::MouseDown { button: MouseButton::from_ll(*button), position: *position },
+            RecordedEvent::MouseUp { button, position } => Event::MouseUp { button: MouseButton::from_ll(*button), position: *position },
+        }
+    }
+}
+
+// Everything a listener saw during one call to PumpProcessor::process_events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedBatch {
+    pub ticks: u32,
+    pub start: InputState,
+    pub events: Vec<RecordedEvent>,
+    pub end: InputState,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Recording {
+    pub batches: Vec<RecordedBatch>,
+}
+
+impl Recording {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        data::load_file(path)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        data::write_file(path, self)
+    }
+}
+
+// The recording is written every SAVE_INTERVAL batches (about 10 seconds at 60 fps) and when the
+// recorder is dropped, so a session that ends with an error or a panic is not lost
+const SAVE_INTERVAL: usize = 600;
+
+pub struct Recorder {
+    path: PathBuf,
+    recording: Recording,
+    current: Option<RecordedBatch>,
+    // Batches already in the file
+    saved_batches: usize,
+}
+
+impl Recorder {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Recorder { path: path.as_ref().to_owned(), recording: Recording::default(), current: None, saved_batches: 0 }
+    }
+
+    pub fn begin_batch(&mut self, ticks: u32, start: &InputState) {
+        self.current = Some(RecordedBatch { ticks, start: start.clone(), events: Vec::new(), end: InputState::default() });
+    }
+
+    pub fn record(&mut self, event: &Event) {
+        if let (Some(batch), Some(event)) = (self.current.as_mut(), RecordedEvent::from_event(event)) {
+            batch.events.push(event);
+        }
+    }
+
+    pub fn end_batch(&mut self, end: &InputState) {
+        if let Some(mut batch) = self.current.take() {
+            batch.end = end.clone();
+            self.recording.batches.push(batch);
+        }
+        if self.recording.batches.len() - self.saved_batches >= SAVE_INTERVAL {
+            if let Err(e) = self.save() {
+                println!("Could not save the recording: {:?}", e);
+            }
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), Error> {
+        println!("Saving {} recorded batches to {:?}", self.recording.batches.len(), self.path);
+        self.recording.save(&self.path)?;
+        self.saved_batches = self.recording.batches.len();
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if self.saved_batches < self.recording.batches.len() {
+            if let Err(e) = self.save() {
+                println!("Could not save the recording: {:?}", e);
+            }
+        }
+    }
+}
+
+// Plays a recording back batch by batch, ticks included. PumpProcessor uses it for --replay, and as
+// an InputSource it replays a session without SDL, e.g. in tests.
+pub struct Replay {
+    recording: Recording,
+    next_batch: usize,
+    input: InputState,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Replay { recording, next_batch: 0, input: InputState::default() }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_batch >= self.recording.batches.len()
+    }
+}
+
+impl InputSource for Replay {
+    // Once the recording is over batches are empty and keep the last input state
+    fn process_events<'ttf, T: RenderTarget, L: EventListener<'ttf, T> + ?Sized>(&mut self, state: &mut GameState<'ttf>, listener: &mut L) {
+        let batch = match self.recording.batches.get(self.next_batch) {
+            Some(batch) => batch,
+            None => {
+                listener.batch_start(state, &self.input);
+                listener.batch_end(state, &self.input);
+                return;
+            }
+        };
+        self.next_batch += 1;
+        state.ticks_to_process = batch.ticks;
+        listener.batch_start(state, &batch.start);
+        for event in &batch.events {
+            listener.process_event(state, &event.to_event());
+        }
+        listener.batch_end(state, &batch.end);
+        self.input = batch.end.clone();
+    }
+
+    fn input(&self) -> &InputState {
+        &self.input
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::surface::Surface;
+
+    use crate::event::EventResult;
+
+    use super::*;
+
+    // Remembers the ticks and events of every batch it sees
+    #[derive(Default)]
+    struct BatchLog {
+        batches: Vec<(u32, Vec<Event>)>,
+    }
+
+    impl<'ttf> EventListener<'ttf, Surface<'static>> for BatchLog {
+        fn batch_start(&mut self, state: &mut GameState<'ttf>, _input: &InputState) -> Option<EventResult<'ttf, Surface<'static>>> {
+            self.batches.push((state.ticks_to_process, Vec::new()));
+            None
+        }
+
+        fn process_event(&mut self, _state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, Surface<'static>>> {
+            self.batches.last_mut().unwrap().1.push(event.clone());
+            None
+        }
+    }
+
+    fn record(path: &Path) {
+        let mut recorder = Recorder::new(path);
+        recorder.begin_batch(16, &InputState::default());
+        recorder.record(&Event::ActionDown { action: Action::Select });
+        recorder.end_batch(&InputState::new(vec![Action::Select]));
+        recorder.begin_batch(20, &InputState::new(vec![Action::Select]));
+        recorder.record(&Event::ActionUp { action: Action::Select });
+        recorder.end_batch(&InputState::default());
+    }
+
+    #[test]
+    fn dropped_recorder_saves() {
+        let path = std::env::temp_dir().join("rpg_dropped_recorder.json");
+        let _ = std::fs::remove_file(&path);
+        record(&path);
+        assert_eq!(Recording::load(&path).unwrap().batches.len(), 2);
+    }
+
+    #[test]
+    fn replay_without_sdl() {
+        let path = std::env::temp_dir().join("rpg_replay.json");
+        record(&path);
+        let mut replay = Replay::new(Recording::load(&path).unwrap());
+        let mut state = GameState::headless();
+        let mut log = BatchLog::default();
+
+        replay.process_events(&mut state, &mut log);
+        assert_eq!(state.ticks_to_process, 16);
+        assert_eq!(replay.input().pressed(), &[Action::Select]);
+        replay.process_events(&mut state, &mut log);
+        assert_eq!(state.ticks_to_process, 20);
+        assert!(replay.input().pressed().is_empty());
+        assert!(replay.is_finished());
+
+        assert_eq!(log.batches.len(), 2);
+        assert!(matches!(log.batches[0].1[..], [Event::ActionDown { action: Action::Select }]));
+        assert!(matches!(log.batches[1].1[..], [Event::ActionUp { action: Action::Select }]));
+
+        // Past the end batches are empty
+        replay.process_events(&mut state, &mut log);
+        assert!(log.batches[2].1.is_empty());
+    }
+}
@@ -6,8 +6,10 @@ use sdl2::surface::Surface;
 use sdl2::ttf::{Font, Sdl2TtfContext};
 
 use crate::{Error, TextureLoader};
+use crate::gfx::spritesheet::SpriteSheet;
 use crate::gfx::texture::Texture;
 use crate::gfx::tileset::Tileset;
+use crate::gfx::window::WindowSkin;
 
 pub type LoadResult<T> = Result<Rc<T>, Error>;
 
@@ -17,10 +19,15 @@ pub trait Resources<'ttf> {
 
     fn get_tileset(&mut self, id: &str) -> LoadResult<Tileset<'ttf>>;
 
+    fn get_sprite_sheet(&mut self, id: &str) -> LoadResult<SpriteSheet<'ttf>>;
+
+    fn get_window_skin(&mut self, id: &str) -> LoadResult<WindowSkin<'ttf>>;
+
     fn load_font(&mut self, path: &Path, size: u16) -> LoadResult<Font<'ttf, 'static>>;
     fn get_font(&mut self, id: &str) -> LoadResult<Font<'ttf, 'static>>;
 
     fn texture_from_surface(&self, surface: Surface) -> Result<Texture<'ttf>, Error>;
+    fn create_target_texture(&self, width: u32, height: u32) -> Result<Texture<'ttf>, Error>;
 }
 
 pub struct CachedResources<'ttf, T> {
@@ -57,6 +64,14 @@ impl<'ttf, T> Resources<'ttf> for CachedResources<'ttf, T> {
         Err(Error::simple("Resource ids are not supported"))
     }
 
+    fn get_sprite_sheet(&mut self, _id: &str) -> LoadResult<SpriteSheet<'ttf>> {
+        Err(Error::simple("Resource ids are not supported"))
+    }
+
+    fn get_window_skin(&mut self, _id: &str) -> LoadResult<WindowSkin<'ttf>> {
+        Err(Error::simple("Resource ids are not supported"))
+    }
+
     fn load_font(&mut self, path: &Path, size: u16) -> LoadResult<Font<'ttf, 'static>> {
         Ok(self.fonts.entry((self.path_to_string(path)?, size))
             .or_insert_with(|| {
@@ -74,4 +89,49 @@ impl<'ttf, T> Resources<'ttf> for CachedResources<'ttf, T> {
     fn texture_from_surface(&self, surface: Surface) -> Result<Texture<'ttf>, Error> {
         self.texture_loader.texture_from_surface(surface)
     }
-}
\ No newline at end of file
+
+    fn create_target_texture(&self, width: u32, height: u32) -> Result<Texture<'ttf>, Error> {
+        self.texture_loader.create_target_texture(width, height)
+    }
+}
+
+// Has nothing to give, for driving scenes without SDL video or ttf, e.g. in tests
+pub struct NullResources {}
+
+impl<'ttf> Resources<'ttf> for NullResources {
+    fn load_texture(&mut self, path: &Path) -> LoadResult<Texture<'ttf>> {
+        Err(Error::simple(format!("No resources to load {:?} from", path)))
+    }
+
+    fn get_texture(&mut self, id: &str) -> LoadResult<Texture<'ttf>> {
+        Err(Error::simple(format!("No resources to get {} from", id)))
+    }
+
+    fn get_tileset(&mut self, id: &str) -> LoadResult<Tileset<'ttf>> {
+        Err(Error::simple(format!("No resources to get {} from", id)))
+    }
+
+    fn get_sprite_sheet(&mut self, id: &str) -> LoadResult<SpriteSheet<'ttf>> {
+        Err(Error::simple(format!("No resources to get {} from", id)))
+    }
+
+    fn get_window_skin(&mut self, id: &str) -> LoadResult<WindowSkin<'ttf>> {
+        Err(Error::simple(format!("No resources to get {} from", id)))
+    }
+
+    fn load_font(&mut self, path: &Path, _size: u16) -> LoadResult<Font<'ttf, 'static>> {
+        Err(Error::simple(format!("No resources to load {:?} from", path)))
+    }
+
+    fn get_font(&mut self, id: &str) -> LoadResult<Font<'ttf, 'static>> {
+        Err(Error::simple(format!("No resources to get {} from", id)))
+    }
+
+    fn texture_from_surface(&self, _surface: Surface) -> Result<Texture<'ttf>, Error> {
+        Err(Error::simple("No resources to create textures with"))
+    }
+
+    fn create_target_texture(&self, _width: u32, _height: u32) -> Result<Texture<'ttf>, Error> {
+        Err(Error::simple("No resources to create textures with"))
+    }
+}
@@ -0,0 +1,323 @@
+use std::rc::Rc;
+
+use sdl2::event::Event as SdlEvent;
+use sdl2::keyboard::Scancode;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::RenderTarget;
+
+use crate::{Error, Event, EventListener, EventResult, GameState, InputState, Resources, Scene};
+use crate::gfx::font::TextFont;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::window::WindowSkin;
+use crate::keymap::{Action, KeyMap, MAX_KEYS_PER_ACTION};
+use crate::point::IntPoint;
+use crate::settings::Settings;
+
+const LINE_HEIGHT: i32 = 40;
+const WINDOW_PADDING: i32 = 16;
+const WINDOW_WIDTH: u32 = 620;
+
+// An action in the base key map (no context) or in one input context's key map
+#[derive(Clone, PartialEq)]
+struct BindingRow {
+    context: Option<String>,
+    action: Action,
+}
+
+impl BindingRow {
+    fn label(&self) -> String {
+        match &self.context {
+            Some(context) => format!("{}: {}", context, self.action.name()),
+            None => self.action.name().to_owned(),
+        }
+    }
+
+    fn key_map(&self, settings: &Settings) -> Rc<KeyMap> {
+        match &self.context {
+            Some(context) => settings.context(context).map(|context| context.key_map.clone()).unwrap_or_default(),
+            None => settings.key_map.clone(),
+        }
+    }
+
+    fn set_key_map(&self, settings: &mut Settings, key_map: KeyMap) {
+        match &self.context {
+            Some(context) => settings.set_context_key_map(context, key_map),
+            None => settings.set_key_map(key_map),
+        }
+    }
+}
+
+// Lets the player rebind every action, the base ones first and then those of each input context.
+// Select on an action waits for the next key press, keys used by another action need to be pressed
+// twice to be moved and hotkeys can not be used. Contexts layer over the base key map, so a key
+// used in both would have one action hide the other: that counts as a conflict too. Actions keep
+// at most MAX_KEYS_PER_ACTION keys, the player is told which key a new one replaced. Changes apply at once and are saved when leaving.
+pub struct KeyBindingsScene<'ttf> {
+    font: Rc<dyn TextFont<'ttf> + 'ttf>,
+    window_skin: Option<Rc<WindowSkin<'ttf>>>,
+    // One row per action, followed by reset and back
+    rows: Vec<BindingRow>,
+    // Copy of each row's key map in the game state, for drawing
+    key_maps: Vec<Rc<KeyMap>>,
+    selected_row: i32,
+    waiting_for_key: Option<usize>,
+    // Key that is bound to another action and was pressed once, with the row of that action
+    pending_conflict: Option<(Scancode, usize)>,
+    message: Option<String>,
+}
+
+impl<'ttf> KeyBindingsScene<'ttf> {
+    pub fn new(font: Rc<dyn TextFont<'ttf> + 'ttf>, window_skin: Option<Rc<WindowSkin<'ttf>>>, settings: &Settings) -> Self {
+        let rows: Vec<BindingRow> = Action::BASE.iter()
+            .map(|action| BindingRow { context: None, action: *action })
+            .chain(settings.contexts.iter().flat_map(|context| context.actions.iter().map(|action| BindingRow { context: Some(context.name.clone()), action: *action })))
+            .collect();
+        let key_maps = rows.iter().map(|row| row.key_map(settings)).collect();
+        KeyBindingsScene { font, window_skin, rows, key_maps, selected_row: 0, waiting_for_key: None, pending_conflict: None, message: None }
+    }
+
+    fn row_count(&self) -> usize {
+        self.rows.len() + 2
+    }
+
+    fn reset_row(&self) -> usize {
+        self.rows.len()
+    }
+
+    fn back_row(&self) -> usize {
+        self.rows.len() + 1
+    }
+
+    fn selected_index(&self) -> usize {
+        crate::utils::positive_mod(self.selected_row, self.row_count())
+    }
+
+    fn row_text(&self, index: usize) -> String {
+        if index == self.reset_row() {
+            return "Reset to defaults".to_owned();
+        }
+        if index == self.back_row() {
+            return "Back".to_owned();
+        }
+        let row = &self.rows[index];
+        if self.waiting_for_key == Some(index) {
+            return format!("{}: ...", row.label());
+        }
+        let keys = self.key_maps[index].get_keys(&row.action)
+            .filter(|keys| !keys.is_empty())
+            .map(|keys| keys.iter().map(|key| key.name()).collect::<Vec<_>>().join(", "))
+            .unwrap_or_else(|| "-".to_owned());
+        format!("{}: {}", row.label(), keys)
+    }
+
+    fn start_waiting(&mut self, state: &mut GameState<'ttf>, index: usize) {
+        self.waiting_for_key = Some(index);
+        self.pending_conflict = None;
+        self.message = Some(format!("Press a key for {} (Escape cancels)", self.rows[index].label()));
+        state.raw_keys = true;
+    }
+
+    fn stop_waiting(&mut self, state: &mut GameState<'ttf>) {
+        self.waiting_for_key = None;
+        self.pending_conflict = None;
+        state.raw_keys = false;
+    }
+
+    // Another row whose key map binds the key, in this row's key map or in one layered with it
+    fn conflict(&self, settings: &Settings, index: usize, key: Scancode) -> Option<usize> {
+        let row = &self.rows[index];
+        self.rows.iter().position(|other| other != row && other.key_map(settings).get_action(&key) == Some(&other.action))
+    }
+
+    fn key_pressed(&mut self, state: &mut GameState<'ttf>, index: usize, key: Scancode) {
+        if key == Scancode::Escape {
+            self.stop_waiting(state);
+            self.message = None;
+            return;
+        }
+        // Hotkeys would take the key before the action gets it
+        if let Some(hotkey) = state.settings.hotkeys.get_hotkey(&key) {
+            self.message = Some(format!("{} is the {} hotkey, press another key", key.name(), hotkey.name()));
+            return;
+        }
+        let row = self.rows[index].clone();
+        if let Some(other) = self.conflict(&state.settings, index, key) {
+            if self.pending_conflict != Some((key, other)) {
+                self.pending_conflict = Some((key, other));
+                self.message = Some(format!("{} is used by {}. Press it again to move it", key.name(), self.rows[other].label()));
+                return;
+            }
+            // Binding takes the key away within the same key map, other key maps lose it here
+            let other = &self.rows[other];
+            if other.context != row.context {
+                let mut other_key_map = other.key_map(&state.settings).as_ref().clone();
+                other_key_map.unbind_key(key);
+                other.set_key_map(&mut state.settings, other_key_map);
+            }
+        }
+        let mut key_map = row.key_map(&state.settings).as_ref().clone();
+        // Binding one key too many drops the oldest one
+        let dropped = key_map.get_keys(&row.action)
+            .filter(|keys| keys.len() >= MAX_KEYS_PER_ACTION && !keys.contains(&key))
+            .and_then(|keys| keys.first().copied());
+        key_map.bind(row.action, key);
+        row.set_key_map(&mut state.settings, key_map);
+        self.stop_waiting(state);
+        self.message = dropped.map(|dropped| format!("{} replaces {} for {}", key.name(), dropped.name(), row.label()));
+    }
+
+    // Leaving is refused while an action has no key, since it could not be used anymore
+    fn leave<T: RenderTarget>(&mut self, state: &mut GameState<'ttf>) -> Option<EventResult<'ttf, T>> {
+        let unbound = self.rows.iter()
+            .find(|row| row.key_map(&state.settings).get_keys(&row.action).map(|keys| keys.is_empty()).unwrap_or(true));
+        if let Some(row) = unbound {
+            self.message = Some(format!("{} needs a key", row.label()));
+            return None;
+        }
+        if let Err(e) = state.settings.save() {
+            println!("Could not save settings: {:?}", e);
+        }
+        Some(EventResult::PopScene)
+    }
+}
+
+impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for KeyBindingsScene<'ttf> {
+    fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
+        if let Some(index) = self.waiting_for_key {
+            if let Event::Sdl(SdlEvent::KeyDown { scancode: Some(key), repeat: false, .. }) = event {
+                self.key_pressed(state, index, *key);
+            }
+            return None;
+        }
+        match event {
+            Event::ActionDown { action: Action::Up } | Event::ActionRepeat { action: Action::Up } => {
+                self.selected_row -= 1;
+            }
+            Event::ActionDown { action: Action::Down } | Event::ActionRepeat { action: Action::Down } => {
+                self.selected_row += 1;
+            }
+            Event::ActionDown { action: Action::Back } => return self.leave(state),
+            Event::ActionDown { action: Action::Select } => {
+                let index = self.selected_index();
+                if index == self.reset_row() {
+                    state.settings.reset_key_maps();
+                    self.message = Some("Keys reset to defaults".to_owned());
+                } else if index == self.back_row() {
+                    return self.leave(state);
+                } else {
+                    self.start_waiting(state, index);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+pub const KEY_BINDINGS: &str = "key bindings";
+
+impl<'ttf, T: RenderTarget> Scene<'ttf, T> for KeyBindingsScene<'ttf> {
+    fn name(&self) -> &'static str { KEY_BINDINGS }
+
+    fn on_exit(&mut self, state: &mut GameState<'ttf>) {
+        self.stop_waiting(state);
+    }
+
+    fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error> {
+        renderer.with_offset(IntPoint::new(200, 120), |renderer| {
+            let selected_index = self.selected_index();
+            let row_count = self.row_count();
+            if let Some(skin) = &self.window_skin {
+                let window_height = (LINE_HEIGHT * row_count as i32 + WINDOW_PADDING) as u32;
+                skin.draw_window(renderer, Rect::new(-WINDOW_PADDING, -WINDOW_PADDING / 2, WINDOW_WIDTH, window_height))?;
+                skin.draw_cursor(renderer, Rect::new(-WINDOW_PADDING / 2, LINE_HEIGHT * selected_index as i32, WINDOW_WIDTH - WINDOW_PADDING as u32, LINE_HEIGHT as u32))?;
+            }
+            for index in 0..row_count {
+                let text = self.font.render(&self.row_text(index), if index == selected_index { Color::RED } else { Color::WHITE }, resources)?;
+                text.draw(renderer, IntPoint::new(0, LINE_HEIGHT * index as i32))?;
+            }
+            if let Some(message) = &self.message {
+                let text = self.font.render(message, Color::YELLOW, resources)?;
+                text.draw(renderer, IntPoint::new(0, LINE_HEIGHT * (row_count as i32 + 1)))?;
+            }
+            Ok(())
+        })
+    }
+
+    fn update(&mut self, state: &mut GameState<'ttf>, _input: &InputState) -> Option<EventResult<'ttf, T>> {
+        self.key_maps = self.rows.iter().map(|row| row.key_map(&state.settings)).collect();
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sdl2::keyboard::Mod;
+    use sdl2::surface::Surface;
+
+    use crate::gfx::font::BitmapFont;
+    use crate::input_context::MAP_CONTEXT;
+
+    use super::*;
+
+    fn press(scene: &mut KeyBindingsScene<'static>, state: &mut GameState<'static>, key: Scancode) {
+        let event = Event::Sdl(SdlEvent::KeyDown { timestamp: 0, window_id: 0, keycode: None, scancode: Some(key), keymod: Mod::NOMOD, repeat: false });
+        let _: Option<EventResult<Surface<'static>>> = scene.process_event(state, &event);
+    }
+
+    fn row(scene: &KeyBindingsScene, context: Option<&str>, action: Action) -> usize {
+        scene.rows.iter().position(|row| row.context.as_deref() == context && row.action == action).unwrap()
+    }
+
+    #[test]
+    fn keys_shadowed_by_a_context_conflict() {
+        let mut state = GameState::headless();
+        let font = Rc::new(BitmapFont::new(Vec::new(), HashMap::new(), HashMap::new(), 16));
+        let mut scene = KeyBindingsScene::new(font, None, &state.settings);
+
+        // Space is the map's Interact key, it would hide Select on the map
+        let select = row(&scene, None, Action::Select);
+        scene.start_waiting(&mut state, select);
+        press(&mut scene, &mut state, Scancode::Space);
+        assert_eq!(scene.waiting_for_key, Some(select));
+        assert_eq!(state.settings.key_map.get_action(&Scancode::Space), None);
+
+        press(&mut scene, &mut state, Scancode::Space);
+        assert_eq!(scene.waiting_for_key, None);
+        assert_eq!(state.settings.key_map.get_action(&Scancode::Space), Some(&Action::Select));
+        assert_eq!(state.settings.context(MAP_CONTEXT).unwrap().key_map.get_action(&Scancode::Space), None);
+    }
+
+    #[test]
+    fn replaced_keys_are_reported() {
+        let mut state = GameState::headless();
+        let font = Rc::new(BitmapFont::new(Vec::new(), HashMap::new(), HashMap::new(), 16));
+        let mut scene = KeyBindingsScene::new(font, None, &state.settings);
+        let up = row(&scene, None, Action::Up);
+
+        scene.start_waiting(&mut state, up);
+        press(&mut scene, &mut state, Scancode::W);
+        assert_eq!(scene.message, None);
+
+        scene.start_waiting(&mut state, up);
+        press(&mut scene, &mut state, Scancode::I);
+        assert!(scene.message.is_some());
+        assert_eq!(state.settings.key_map.get_keys(&Action::Up), Some(&vec![Scancode::W, Scancode::I]));
+    }
+
+    #[test]
+    fn hotkeys_can_not_be_bound() {
+        let mut state = GameState::headless();
+        let font = Rc::new(BitmapFont::new(Vec::new(), HashMap::new(), HashMap::new(), 16));
+        let mut scene = KeyBindingsScene::new(font, None, &state.settings);
+        let interact = row(&scene, Some(MAP_CONTEXT), Action::Interact);
+        scene.start_waiting(&mut state, interact);
+        press(&mut scene, &mut state, Scancode::F5);
+        press(&mut scene, &mut state, Scancode::F5);
+        assert_eq!(scene.waiting_for_key, Some(interact));
+        assert_eq!(state.settings.context(MAP_CONTEXT).unwrap().key_map.get_action(&Scancode::F5), None);
+    }
+}
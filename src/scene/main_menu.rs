@@ -1,16 +1,18 @@
 use std::rc::Rc;
 
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
 use sdl2::render::RenderTarget;
-use sdl2::ttf::Font;
 
-use crate::{Error, Event, EventListener, EventResult, GameState, MapData, Resources, Scene, SpriteSheet};
-use crate::gfx::animation::BasicCharAnimation;
+use crate::{Error, Event, EventListener, EventResult, GameState, InputState, MapData, Resources, Scene};
+use crate::game_event::GameEvent;
+use crate::gfx::font::TextFont;
 use crate::gfx::renderer::Renderer;
+use crate::gfx::window::WindowSkin;
 use crate::keymap::Action;
 use crate::point::IntPoint;
+use crate::scene::key_bindings::KeyBindingsScene;
 use crate::scene::map::MapScene;
+use crate::scene::menu::Menu;
+use crate::scene::text_input::TextInputScene;
 
 #[derive(PartialEq)]
 enum MenuOption {
@@ -20,7 +22,7 @@ enum MenuOption {
 }
 
 impl MenuOption {
-    fn text(&self) -> &str {
+    fn text(&self) -> &'static str {
         match self {
             MenuOption::START => "Start!",
             MenuOption::SETTINGS => "Settings",
@@ -30,64 +32,63 @@ impl MenuOption {
 }
 
 const MENU_OPTIONS: [MenuOption; 3] = [MenuOption::START, MenuOption::SETTINGS, MenuOption::QUIT];
+const MENU_POSITION: IntPoint = IntPoint { x: 300, y: 300 };
+pub const MAIN_MENU: &str = "main menu";
+const CHARACTER_NAME: &str = "character_name";
+const MAX_NAME_LENGTH: usize = 12;
 
 pub struct MainMenu<'ttf> {
-    font: Rc<Font<'ttf, 'static>>,
+    menu: Menu<'ttf>,
     map_data: MapData,
-    selected_option: i32,
 }
 
 impl<'ttf> MainMenu<'ttf> {
-    pub fn new(font: Rc<Font<'ttf, 'static>>, map_data: MapData) -> Self {
-        MainMenu { font, map_data, selected_option: 0 }
+    pub fn new(font: Rc<dyn TextFont<'ttf> + 'ttf>, window_skin: Option<Rc<WindowSkin<'ttf>>>, map_data: MapData) -> Self {
+        let options = MENU_OPTIONS.iter().map(|option| option.text()).collect();
+        MainMenu { menu: Menu::new(font, window_skin, options, MENU_POSITION), map_data }
     }
 
-    fn selected_option(&self) -> &MenuOption {
-        return &MENU_OPTIONS[crate::utils::positive_mod(self.selected_option, MENU_OPTIONS.len())];
+    fn activate<T: RenderTarget>(&mut self, state: &mut GameState<'ttf>, index: usize) -> Option<EventResult<'ttf, T>> {
+        let font = self.menu.font().clone();
+        let window_skin = self.menu.window_skin().clone();
+        match MENU_OPTIONS[index] {
+            MenuOption::START => {
+                let scene = TextInputScene::new(font, window_skin, CHARACTER_NAME, "Name your hero", "", MAX_NAME_LENGTH);
+                Some(EventResult::PushScene(Box::new(scene)))
+            }
+            MenuOption::QUIT => Some(EventResult::Quit),
+            MenuOption::SETTINGS => {
+                let scene = KeyBindingsScene::new(font, window_skin, &state.settings);
+                Some(EventResult::PushScene(Box::new(scene)))
+            }
+        }
     }
 }
 
 impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for MainMenu<'ttf> {
+    fn batch_start(&mut self, state: &mut GameState<'ttf>, _input: &InputState) -> Option<EventResult<'ttf, T>> {
+        // The map starts once the hero has a name
+        let name = state.take_text_input(CHARACTER_NAME)?;
+        println!("Welcome, {}!", name);
+        state.events.publish(GameEvent::HeroNamed { name });
+        let mut scene = MapScene::load(&self.map_data, state.resources.as_mut()).unwrap();
+        scene.set_menu_style(self.menu.font().clone(), self.menu.window_skin().clone());
+        Some(EventResult::PushScene(Box::new(scene)))
+    }
+
     fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
-        match event {
-            Event::ActionDown { action: Action::Up } => {
-                self.selected_option -= 1;
-            }
-            Event::ActionDown { action: Action::Down } => {
-                self.selected_option += 1;
-            }
-            Event::ActionDown { action: Action::Back } => {
-                state.running = false;
-            }
-            Event::ActionDown { action: Action::Select } => {
-                match *self.selected_option() {
-                    MenuOption::START => {
-                        let character = self.map_data.character.load(&mut state.resources).unwrap();
-                        let sprite_width = character.width() / 4;
-                        let sprite_height = character.height() / 4;
-                        let animation = BasicCharAnimation::new(Rc::new(SpriteSheet::new(character, sprite_width, sprite_height)));
-                        let tiles = self.map_data.tileset.load(&mut state.resources).unwrap();
-                        return Some(EventResult::PushScene(Box::new(MapScene::new(animation, tiles, self.map_data.tiles.to_tiles()))));
-                    }
-                    MenuOption::QUIT => state.running = false,
-                    MenuOption::SETTINGS => println!("No settings for you!"),
-                }
-            }
-            _ => {}
+        if let Event::ActionDown { action: Action::Back } = event {
+            return Some(EventResult::Quit);
         }
-        None
+        let index = self.menu.process_event(event)?;
+        self.activate(state, index)
     }
 }
 
 impl<'ttf, T: RenderTarget> Scene<'ttf, T> for MainMenu<'ttf> {
+    fn name(&self) -> &'static str { MAIN_MENU }
+
     fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error> {
-        renderer.with_offset(IntPoint::new(300, 300), |renderer| {
-            for (index, option) in MENU_OPTIONS.iter().enumerate() {
-                let surface = self.font.render(option.text()).blended(if option == self.selected_option() { Color::RED } else { Color::WHITE })?;
-                let texture = resources.texture_from_surface(surface)?;
-                renderer.copy(&texture, None, Rect::new(0, 50 * (index as i32), texture.width(), texture.height()))?;
-            }
-            Ok(())
-        })
+        self.menu.draw(renderer, resources)
     }
-}
\ No newline at end of file
+}
@@ -1,15 +1,27 @@
+use std::cell::Cell;
 use std::rc::Rc;
 
+use sdl2::mouse::MouseButton;
 use sdl2::rect::Rect;
 use sdl2::render::RenderTarget;
 
-use crate::{Error, Event, EventListener, EventResult, GameState, InputState, Point, Resources, Scene};
+use crate::{Error, Event, EventListener, EventResult, GameState, InputState, MapData, Point, Resources, Scene, SpriteSheet};
 use crate::direction::Direction;
+use crate::game_event::{GameEvent, GameEventSubscriber, SubscriberId};
 use crate::gfx::animation::{Animation, BasicCharAnimation, Oriented, Ticker};
+use crate::gfx::font::TextFont;
+use crate::gfx::lighting::Lighting;
+use crate::gfx::minimap::Minimap;
+use crate::gfx::particles::Emitter;
+use crate::gfx::plane::Plane;
 use crate::gfx::renderer::Renderer;
 use crate::gfx::tileset::Tileset;
+use crate::gfx::weather::{Weather, WeatherKind};
+use crate::gfx::window::WindowSkin;
+use crate::input_context::MAP_CONTEXT;
 use crate::keymap::Action;
 use crate::point::IntPoint;
+use crate::scene::pause_menu::PauseMenu;
 
 pub type Layer<T> = Vec<Vec<T>>;
 pub type Tiles<T> = Vec<Layer<T>>;
@@ -19,8 +31,37 @@ pub struct MapScene<'tx> {
     tileset: Rc<Tileset<'tx>>,
     tiles: Tiles<u32>,
     character_position: Point<f32>,
+    emitters: Vec<Emitter<'tx>>,
+    minimap: Option<Minimap<'tx>>,
+    parallax: Vec<Plane<'tx>>,
+    fog: Vec<Plane<'tx>>,
+    lighting: Option<Lighting<'tx>>,
+    weather: Weather,
     sprite_x: u32,
     sprite_y: u32,
+    // Feet position the character walks to after a click, until a direction is pressed
+    walk_target: Option<Point<f32>>,
+    // Added to a view position to get the map position, as of the last draw
+    view_to_map: IntPoint,
+    // Style of the pause menu, which is only available once set
+    menu_font: Option<Rc<dyn TextFont<'tx> + 'tx>>,
+    window_skin: Option<Rc<WindowSkin<'tx>>>,
+    // Weather asked for with GameEvent::WeatherChanged, applied on the next update
+    requested_weather: Rc<Cell<Option<(WeatherKind, f32)>>>,
+    // Subscribed to the event bus while the map is on the stack
+    weather_subscriber: Option<SubscriberId>,
+}
+
+struct WeatherSubscriber {
+    requested: Rc<Cell<Option<(WeatherKind, f32)>>>,
+}
+
+impl<'tx> GameEventSubscriber<'tx> for WeatherSubscriber {
+    fn notify(&mut self, _state: &mut GameState<'tx>, event: &GameEvent) {
+        if let GameEvent::WeatherChanged { kind, intensity } = event {
+            self.requested.set(Some((*kind, *intensity)));
+        }
+    }
 }
 
 pub fn to_vec(layer: &[[u32; 20]; 20]) -> Vec<Vec<u32>> {
@@ -29,7 +70,74 @@ pub fn to_vec(layer: &[[u32; 20]; 20]) -> Vec<Vec<u32>> {
 
 impl<'tx> MapScene<'tx> {
     pub fn new(character: BasicCharAnimation<'tx>, tileset: Rc<Tileset<'tx>>, tiles: Vec<Vec<Vec<u32>>>) -> Self {
-        MapScene { character, tileset, tiles, character_position: Point::new(16., 64.), sprite_x: 0, sprite_y: 0 }
+        MapScene { character, tileset, tiles, character_position: Point::new(16., 64.), emitters: Vec::new(), minimap: None, parallax: Vec::new(), fog: Vec::new(), lighting: None, weather: Weather::new(WeatherKind::Clear, 0.), sprite_x: 0, sprite_y: 0, walk_target: None, view_to_map: IntPoint::new(0, 0), menu_font: None, window_skin: None, requested_weather: Rc::new(Cell::new(None)), weather_subscriber: None }
+    }
+
+    pub fn load(map_data: &MapData, resources: &mut dyn Resources<'tx>) -> Result<Self, Error> {
+        let character = map_data.character.load(resources)?;
+        let sprite_width = character.width() / 4;
+        let sprite_height = character.height() / 4;
+        let animation = BasicCharAnimation::new(Rc::new(SpriteSheet::new(character, sprite_width, sprite_height)));
+        let tiles = map_data.tileset.load(resources)?;
+        let mut scene = MapScene::new(animation, tiles, map_data.tiles.to_tiles());
+        for (index, placed) in map_data.emitters.iter().enumerate() {
+            scene.emitters.push(Emitter::new(placed.emitter.load(resources)?, placed.position, index as u32 + 1));
+        }
+        scene.minimap = map_data.minimap.as_ref().map(|minimap| minimap.load(&map_data.tileset)).transpose()?;
+        for plane in &map_data.parallax {
+            scene.parallax.push(plane.load(resources)?);
+        }
+        for plane in &map_data.fog {
+            scene.fog.push(plane.load(resources)?);
+        }
+        scene.lighting = map_data.lighting.as_ref().map(|lighting| lighting.to_lighting());
+        if let Some(weather) = &map_data.weather {
+            scene.weather = weather.to_weather();
+        }
+        Ok(scene)
+    }
+
+    pub fn set_menu_style(&mut self, font: Rc<dyn TextFont<'tx> + 'tx>, window_skin: Option<Rc<WindowSkin<'tx>>>) {
+        self.menu_font = Some(font);
+        self.window_skin = window_skin;
+    }
+
+    pub fn set_weather(&mut self, kind: WeatherKind, intensity: f32) {
+        self.weather.set(kind, intensity);
+    }
+
+    fn pixel_size(&self) -> (u32, u32) {
+        let rows = self.tiles.iter().map(|layer| layer.len()).max().unwrap_or(0) as u32;
+        let columns = self.tiles.iter().flat_map(|layer| layer.iter().map(|row| row.len())).max().unwrap_or(0) as u32;
+        (columns * self.tileset.tile_width(), rows * self.tileset.tile_height())
+    }
+
+    // Map position shown at the top left of the view: follows the character, but stays inside the map
+    fn camera(&self, view_size: (u32, u32)) -> IntPoint {
+        let (map_width, map_height) = self.pixel_size();
+        let character = self.character_position.truncate();
+        IntPoint::new(
+            (character.x - view_size.0 as i32 / 2).min(map_width as i32 - view_size.0 as i32).max(0),
+            (character.y - view_size.1 as i32 / 2).min(map_height as i32 - view_size.1 as i32).max(0),
+        )
+    }
+
+    // Tile under the character's feet
+    fn character_tile(&self) -> IntPoint {
+        let feet = self.character_position.truncate();
+        IntPoint::new(feet.x.div_euclid(self.tileset.tile_width() as i32), (feet.y - 1).div_euclid(self.tileset.tile_height() as i32))
+    }
+
+    // Bottom middle of the tile under a view position, where the character's feet go
+    fn tile_target(&self, position: IntPoint) -> Option<Point<f32>> {
+        let map = position + self.view_to_map;
+        let (map_width, map_height) = self.pixel_size();
+        if map.x < 0 || map.y < 0 || map.x >= map_width as i32 || map.y >= map_height as i32 {
+            return None;
+        }
+        let (tile_width, tile_height) = (self.tileset.tile_width() as i32, self.tileset.tile_height() as i32);
+        let tile = IntPoint::new(map.x / tile_width, map.y / tile_height);
+        Some(Point::new((tile.x as f32 + 0.5) * tile_width as f32, ((tile.y + 1) * tile_height) as f32))
     }
 
     fn print<T: RenderTarget>(&self, layer: &Vec<Vec<u32>>, renderer: &mut Renderer<T>) -> Result<(), Error> {
@@ -44,7 +152,7 @@ impl<'tx> MapScene<'tx> {
 
                 self.tileset.get_tile(*value)
                     .and_then(|tile_rect| {
-                        renderer.copy(tile_rect.texture(), tile_rect.rect(), dst)?;
+                        renderer.copy_texture_rect(&tile_rect, dst)?;
                         Ok(())
                     })?;
             }
@@ -62,9 +170,22 @@ impl<'tx> MapScene<'tx> {
 
 
 impl<'tx, T: RenderTarget> EventListener<'tx, T> for MapScene<'tx> {
-    fn process_event(&mut self, _state: &mut GameState, event: &Event) -> Option<EventResult<'tx, T>> {
+    fn process_event(&mut self, state: &mut GameState, event: &Event) -> Option<EventResult<'tx, T>> {
         match event {
             Event::ActionDown { action: Action::Back } => Some(EventResult::PopScene),
+            // The map keeps animating under the pause menu. Without a font there is no menu to show, the action is ignored.
+            Event::ActionDown { action: Action::Menu } => {
+                let font = self.menu_font.as_ref()?;
+                Some(EventResult::PushOverlay { scene: Box::new(PauseMenu::new(font.clone(), self.window_skin.clone())), update_below: true })
+            }
+            Event::ActionDown { action: Action::Interact } => {
+                state.events.publish(GameEvent::Interacted { tile: self.character_tile() });
+                None
+            }
+            Event::ActionDown { action: Action::ToggleMinimap } => {
+                if let Some(minimap) = self.minimap.as_mut() { minimap.toggle(); }
+                None
+            }
             Event::ActionDown { action: Action::Left } => {
                 if self.sprite_x > 0 { self.sprite_x -= 1; }
                 None
@@ -81,12 +202,82 @@ impl<'tx, T: RenderTarget> EventListener<'tx, T> for MapScene<'tx> {
                 if self.sprite_y > 0 { self.sprite_y -= 1; }
                 None
             }
+            Event::MouseDown { button: MouseButton::Left, position } => {
+                self.walk_target = self.tile_target(*position);
+                None
+            }
             _ => None,
         }
     }
+}
+
+pub const MAP: &str = "map";
+
+impl<'tx, T: RenderTarget> Scene<'tx, T> for MapScene<'tx> {
+    fn name(&self) -> &'static str { MAP }
 
+    fn input_context(&self) -> Option<&str> { Some(MAP_CONTEXT) }
 
-    fn batch_end(&mut self, state: &mut GameState, input: &InputState) -> Option<EventResult<'tx, T>> {
+    fn on_enter(&mut self, state: &mut GameState<'tx>) {
+        state.events.publish(GameEvent::MapEntered);
+        self.weather_subscriber = Some(state.events.subscribe(Box::new(WeatherSubscriber { requested: self.requested_weather.clone() })));
+    }
+
+    fn on_exit(&mut self, state: &mut GameState<'tx>) {
+        state.events.publish(GameEvent::MapLeft);
+        if let Some(id) = self.weather_subscriber.take() {
+            state.events.unsubscribe(id);
+        }
+    }
+
+    // A click made before the pause menu opened should not keep walking under it
+    fn on_pause(&mut self, _state: &mut GameState<'tx>) {
+        self.walk_target = None;
+    }
+
+    fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'tx>) -> Result<(), Error> {
+        let camera = self.camera(renderer.view_size());
+        self.view_to_map = camera - renderer.offset();
+        for plane in &self.parallax {
+            plane.draw(renderer, camera)?;
+        }
+
+        renderer.with_offset(-camera, |renderer| {
+            for layer in &self.tiles {
+                self.print(layer, renderer)?;
+            }
+
+            self.character.draw_at(renderer, self.character_position.truncate())?;
+
+            for emitter in &self.emitters {
+                emitter.draw(renderer)?;
+            }
+            Ok(())
+        })?;
+
+        if let Some(lighting) = self.lighting.as_mut() {
+            // Lights sit around the middle of the character sprite rather than its feet
+            let character_center = self.character_position - Point::new(0., self.tileset.tile_height() as f32 / 2.);
+            lighting.draw(renderer, resources, camera, character_center, (self.tileset.tile_width(), self.tileset.tile_height()))?;
+        }
+
+        for plane in &self.fog {
+            plane.draw(renderer, camera)?;
+        }
+
+        self.weather.draw(renderer)?;
+
+        if let Some(minimap) = self.minimap.as_mut() {
+            let character_tile = Point::new(
+                self.character_position.x / self.tileset.tile_width() as f32,
+                self.character_position.y / self.tileset.tile_height() as f32,
+            );
+            minimap.draw(renderer, resources, &self.tiles, character_tile)?;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, state: &mut GameState, input: &InputState) -> Option<EventResult<'tx, T>> {
         let mut direction_point = IntPoint::new(0, 0);
         for (action, direction) in MapScene::ACTION_TO_DIRECTION {
             if input.is_action_pressed(action) {
@@ -94,27 +285,46 @@ impl<'tx, T: RenderTarget> EventListener<'tx, T> for MapScene<'tx> {
             }
         }
 
+        let speed = if input.is_action_pressed(Action::Run) { 0.6 } else { 0.3 };
+        let distance: f32 = state.ticks_to_process as f32 * speed;
         if let Some(direction) = direction_point.direction() {
             // We are moving
+            self.walk_target = None;
             self.character.advance(state.ticks_to_process);
             self.character.point_to(direction);
-            let distance: f32 = state.ticks_to_process as f32 * 0.3;
             self.character_position += direction.to_unit_point().invert_y() * distance;
+        } else if let Some(target) = self.walk_target {
+            let delta = target - self.character_position;
+            let length = (delta.x * delta.x + delta.y * delta.y).sqrt();
+            if length <= distance {
+                self.character_position = target;
+                self.walk_target = None;
+                self.character.reset();
+            } else {
+                self.character.advance(state.ticks_to_process);
+                if let Some(direction) = delta.invert_y().direction() {
+                    self.character.point_to(direction);
+                }
+                self.character_position += delta * (distance / length);
+            }
         } else {
             self.character.reset();
         }
 
-        None
-    }
-}
-
-impl<'tx, T: RenderTarget> Scene<'tx, T> for MapScene<'tx> {
-    fn draw(&mut self, renderer: &mut Renderer<T>, _resources: &mut dyn Resources<'tx>) -> Result<(), Error> {
-        for layer in &self.tiles {
-            self.print(layer, renderer)?;
+        for emitter in self.emitters.iter_mut() {
+            emitter.advance(state.ticks_to_process);
+        }
+        for plane in self.parallax.iter_mut().chain(self.fog.iter_mut()) {
+            plane.advance(state.ticks_to_process);
+        }
+        if let Some(lighting) = self.lighting.as_mut() {
+            lighting.advance(state.ticks_to_process);
         }
+        if let Some((kind, intensity)) = self.requested_weather.take() {
+            self.set_weather(kind, intensity);
+        }
+        self.weather.advance(state.ticks_to_process);
 
-        self.character.draw_at(renderer, self.character_position.truncate())?;
-        Ok(())
+        None
     }
-}
\ No newline at end of file
+}
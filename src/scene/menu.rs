@@ -0,0 +1,154 @@
+use std::rc::Rc;
+
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::RenderTarget;
+
+use crate::{Error, Event, Resources};
+use crate::gfx::font::TextFont;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::window::WindowSkin;
+use crate::keymap::Action;
+use crate::point::IntPoint;
+
+const LINE_HEIGHT: i32 = 50;
+const WINDOW_PADDING: i32 = 16;
+
+// Options listed from top to bottom in a window, picked with Up/Down and Select or with the mouse.
+// Scenes keep what the options do, the menu only tells which one was activated.
+pub struct Menu<'ttf> {
+    font: Rc<dyn TextFont<'ttf> + 'ttf>,
+    window_skin: Option<Rc<WindowSkin<'ttf>>>,
+    options: Vec<&'static str>,
+    position: IntPoint,
+    selected_option: i32,
+    // Top left of the menu on screen at the last draw, for mouse hit tests
+    origin: IntPoint,
+}
+
+impl<'ttf> Menu<'ttf> {
+    pub fn new(font: Rc<dyn TextFont<'ttf> + 'ttf>, window_skin: Option<Rc<WindowSkin<'ttf>>>, options: Vec<&'static str>, position: IntPoint) -> Self {
+        Menu { font, window_skin, options, position, selected_option: 0, origin: position }
+    }
+
+    pub fn font(&self) -> &Rc<dyn TextFont<'ttf> + 'ttf> {
+        &self.font
+    }
+
+    pub fn window_skin(&self) -> &Option<Rc<WindowSkin<'ttf>>> {
+        &self.window_skin
+    }
+
+    pub fn selected_index(&self) -> usize {
+        crate::utils::positive_mod(self.selected_option, self.options.len())
+    }
+
+    fn text_width(&self) -> i32 {
+        self.options.iter()
+            .filter_map(|option| self.font.size_of(option).ok())
+            .map(|(width, _)| width as i32)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn option_at(&self, position: IntPoint) -> Option<usize> {
+        let local = position - self.origin;
+        if local.x < -WINDOW_PADDING || local.x >= self.text_width() + WINDOW_PADDING || local.y < 0 {
+            return None;
+        }
+        Some((local.y / LINE_HEIGHT) as usize).filter(|index| *index < self.options.len())
+    }
+
+    // Moves the selection, returns the index of the option activated by the event if any
+    pub fn process_event(&mut self, event: &Event) -> Option<usize> {
+        match event {
+            Event::ActionDown { action: Action::Up } | Event::ActionRepeat { action: Action::Up } => {
+                self.selected_option -= 1;
+            }
+            Event::ActionDown { action: Action::Down } | Event::ActionRepeat { action: Action::Down } => {
+                self.selected_option += 1;
+            }
+            Event::ActionDown { action: Action::Select } => return Some(self.selected_index()),
+            Event::MouseMove { position } => {
+                if let Some(index) = self.option_at(*position) {
+                    self.selected_option = index as i32;
+                }
+            }
+            Event::MouseDown { button: MouseButton::Left, position } => {
+                if let Some(index) = self.option_at(*position) {
+                    self.selected_option = index as i32;
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn draw<T: RenderTarget>(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error> {
+        self.origin = renderer.offset() + self.position;
+        renderer.with_offset(self.position, |renderer| {
+            let selected_index = self.selected_index();
+            if let Some(skin) = &self.window_skin {
+                let window_width = (self.text_width() + 2 * WINDOW_PADDING) as u32;
+                let window_height = (LINE_HEIGHT * self.options.len() as i32 + WINDOW_PADDING) as u32;
+                skin.draw_window(renderer, Rect::new(-WINDOW_PADDING, -WINDOW_PADDING / 2, window_width, window_height))?;
+                skin.draw_cursor(renderer, Rect::new(-WINDOW_PADDING / 2, LINE_HEIGHT * selected_index as i32, window_width - WINDOW_PADDING as u32, LINE_HEIGHT as u32))?;
+            }
+            for (index, option) in self.options.iter().enumerate() {
+                let text = self.font.render(option, if index == selected_index { Color::RED } else { Color::WHITE }, resources)?;
+                text.draw(renderer, IntPoint::new(0, LINE_HEIGHT * (index as i32)))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::gfx::font::{BitmapFont, Glyph};
+
+    use super::*;
+
+    // Every letter 10 pixels wide, no textures needed to measure text
+    fn menu(options: Vec<&'static str>) -> Menu<'static> {
+        let glyphs = ('a'..='z').map(|character| (character, Glyph { page: 0, rect: None, offset: IntPoint::new(0, 0), advance: 10 })).collect();
+        let font = Rc::new(BitmapFont::new(Vec::new(), glyphs, HashMap::new(), 20));
+        Menu::new(font, None, options, IntPoint::new(100, 200))
+    }
+
+    #[test]
+    fn keys_wrap_around() {
+        let mut menu = menu(vec!["one", "two", "three"]);
+        menu.process_event(&Event::ActionDown { action: Action::Up });
+        assert_eq!(menu.selected_index(), 2);
+        menu.process_event(&Event::ActionRepeat { action: Action::Down });
+        assert_eq!(menu.selected_index(), 0);
+        menu.process_event(&Event::ActionDown { action: Action::Down });
+        assert_eq!(menu.process_event(&Event::ActionUp { action: Action::Down }), None);
+        assert_eq!(menu.process_event(&Event::ActionDown { action: Action::Select }), Some(1));
+    }
+
+    #[test]
+    fn mouse_hits_options() {
+        let mut menu = menu(vec!["one", "two", "three"]);
+        // "three" is the widest option, the window adds its padding around it
+        assert_eq!(menu.option_at(IntPoint::new(100, 200)), Some(0));
+        assert_eq!(menu.option_at(IntPoint::new(100 - WINDOW_PADDING, 250)), Some(1));
+        assert_eq!(menu.option_at(IntPoint::new(100 + 50 + WINDOW_PADDING - 1, 349)), Some(2));
+        assert_eq!(menu.option_at(IntPoint::new(100 + 50 + WINDOW_PADDING, 200)), None);
+        assert_eq!(menu.option_at(IntPoint::new(100, 199)), None);
+        assert_eq!(menu.option_at(IntPoint::new(100, 350)), None);
+
+        menu.process_event(&Event::MouseMove { position: IntPoint::new(110, 260) });
+        assert_eq!(menu.selected_index(), 1);
+        assert_eq!(menu.process_event(&Event::MouseDown { button: MouseButton::Right, position: IntPoint::new(110, 310) }), None);
+        assert_eq!(menu.process_event(&Event::MouseDown { button: MouseButton::Left, position: IntPoint::new(10, 310) }), None);
+        assert_eq!(menu.selected_index(), 1);
+        assert_eq!(menu.process_event(&Event::MouseDown { button: MouseButton::Left, position: IntPoint::new(110, 310) }), Some(2));
+        assert_eq!(menu.selected_index(), 2);
+    }
+}
@@ -1,12 +1,37 @@
 use sdl2::render::RenderTarget;
 
-use crate::{Error, EventListener};
+use crate::{Error, EventListener, EventResult, GameState, InputState};
 use crate::gfx::renderer::Renderer;
 use crate::resources::Resources;
 
+pub mod key_bindings;
 pub mod map;
 pub mod main_menu;
+pub mod menu;
+pub mod pause_menu;
+pub mod stack;
+pub mod text_input;
 
+// Scenes get their input through EventListener and advance in update, once per frame after the
+// input was handled. The scene stack calls the on_ hooks on every transition.
 pub trait Scene<'ttf, T: RenderTarget>: EventListener<'ttf, T> {
     fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error>;
+
+    // Game logic, state.ticks_to_process tells how far to advance
+    fn update(&mut self, _state: &mut GameState<'ttf>, _input: &InputState) -> Option<EventResult<'ttf, T>> { None }
+
+    // Pushed on the stack
+    fn on_enter(&mut self, _state: &mut GameState<'ttf>) {}
+    // Removed from the stack
+    fn on_exit(&mut self, _state: &mut GameState<'ttf>) {}
+    // Another scene was pushed over this one
+    fn on_pause(&mut self, _state: &mut GameState<'ttf>) {}
+    // On top again after the scenes over it were removed
+    fn on_resume(&mut self, _state: &mut GameState<'ttf>) {}
+
+    // Used by EventResult::PopTo to find the scene
+    fn name(&self) -> &'static str { "" }
+
+    // Name of the input context (see Settings::contexts) to layer over the base key map while this scene is on top
+    fn input_context(&self) -> Option<&str> { None }
 }
\ No newline at end of file
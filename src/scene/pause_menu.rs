@@ -0,0 +1,79 @@
+use std::rc::Rc;
+
+use sdl2::render::RenderTarget;
+
+use crate::{Error, Event, EventListener, EventResult, GameState, Resources, Scene};
+use crate::gfx::font::TextFont;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::window::WindowSkin;
+use crate::keymap::Action;
+use crate::point::IntPoint;
+use crate::scene::key_bindings::KeyBindingsScene;
+use crate::scene::main_menu::MAIN_MENU;
+use crate::scene::menu::Menu;
+
+#[derive(PartialEq)]
+enum PauseOption {
+    Resume,
+    Settings,
+    TitleScreen,
+    Quit,
+}
+
+impl PauseOption {
+    fn text(&self) -> &'static str {
+        match self {
+            PauseOption::Resume => "Resume",
+            PauseOption::Settings => "Settings",
+            PauseOption::TitleScreen => "Title screen",
+            PauseOption::Quit => "Quit game",
+        }
+    }
+}
+
+const PAUSE_OPTIONS: [PauseOption; 4] = [PauseOption::Resume, PauseOption::Settings, PauseOption::TitleScreen, PauseOption::Quit];
+const MENU_POSITION: IntPoint = IntPoint { x: 380, y: 240 };
+
+// Pushed as an overlay by the map, which stays visible (and animated) beneath it
+pub struct PauseMenu<'ttf> {
+    menu: Menu<'ttf>,
+}
+
+impl<'ttf> PauseMenu<'ttf> {
+    pub fn new(font: Rc<dyn TextFont<'ttf> + 'ttf>, window_skin: Option<Rc<WindowSkin<'ttf>>>) -> Self {
+        let options = PAUSE_OPTIONS.iter().map(|option| option.text()).collect();
+        PauseMenu { menu: Menu::new(font, window_skin, options, MENU_POSITION) }
+    }
+
+    fn activate<T: RenderTarget>(&mut self, state: &mut GameState<'ttf>, index: usize) -> Option<EventResult<'ttf, T>> {
+        match PAUSE_OPTIONS[index] {
+            PauseOption::Resume => Some(EventResult::PopScene),
+            PauseOption::Settings => {
+                let scene = KeyBindingsScene::new(self.menu.font().clone(), self.menu.window_skin().clone(), &state.settings);
+                Some(EventResult::PushScene(Box::new(scene)))
+            }
+            PauseOption::TitleScreen => Some(EventResult::PopTo(MAIN_MENU)),
+            PauseOption::Quit => Some(EventResult::Quit),
+        }
+    }
+}
+
+impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for PauseMenu<'ttf> {
+    fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
+        if let Event::ActionDown { action: Action::Back } = event {
+            return Some(EventResult::PopScene);
+        }
+        let index = self.menu.process_event(event)?;
+        self.activate(state, index)
+    }
+}
+
+pub const PAUSE_MENU: &str = "pause menu";
+
+impl<'ttf, T: RenderTarget> Scene<'ttf, T> for PauseMenu<'ttf> {
+    fn name(&self) -> &'static str { PAUSE_MENU }
+
+    fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error> {
+        self.menu.draw(renderer, resources)
+    }
+}
@@ -0,0 +1,519 @@
+use sdl2::render::RenderTarget;
+
+use crate::{Error, Event, EventListener, EventResult, GameState, InputState, Resources, Scene};
+use crate::game_event::EventBus;
+use crate::gfx::renderer::Renderer;
+use crate::input_source::InputSource;
+
+// A scene on the stack, with how it was pushed
+struct StackEntry<'ttf, T: RenderTarget> {
+    scene: Box<dyn Scene<'ttf, T> + 'ttf>,
+    // Pushed with EventResult::PushOverlay, the scenes beneath are drawn first
+    overlay: bool,
+    // The scenes beneath keep being updated while this overlay is on top
+    update_below: bool,
+}
+
+// Global listeners see everything before the scenes, in the order they were added. For events the
+// first listener that returns a result consumes the event: its result is processed and neither the
+// listeners after it nor the active scene see the event. Results from batch_start and batch_end are
+// all processed, in order, before the active scene's.
+pub struct SceneStack<'ttf, T: RenderTarget> {
+    pub global_listeners: Vec<Box<dyn EventListener<'ttf, T>>>,
+    stack: Vec<StackEntry<'ttf, T>>,
+}
+
+impl<'ttf, T: RenderTarget> SceneStack<'ttf, T> {
+    pub fn new(global_listeners: Vec<Box<dyn EventListener<'ttf, T>>>) -> Self {
+        SceneStack { global_listeners, stack: Vec::new() }
+    }
+
+    pub fn push(&mut self, state: &mut GameState<'ttf>, mut scene: Box<dyn Scene<'ttf, T> + 'ttf>, overlay: bool, update_below: bool) {
+        if let Some(top) = self.active_scene_mut() {
+            top.on_pause(state);
+        }
+        scene.on_enter(state);
+        self.stack.push(StackEntry { scene, overlay, update_below });
+        self.update_input_contexts(state);
+    }
+
+    // Removes the top scene without resuming the one beneath
+    fn remove_top(&mut self, state: &mut GameState<'ttf>) -> bool {
+        match self.stack.pop() {
+            Some(mut entry) => {
+                entry.scene.on_exit(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn pop(&mut self, state: &mut GameState<'ttf>, count: usize) {
+        let mut removed = false;
+        for _ in 0..count {
+            removed |= self.remove_top(state);
+        }
+        if let (true, Some(top)) = (removed, self.active_scene_mut()) {
+            top.on_resume(state);
+        }
+        self.update_input_contexts(state);
+    }
+
+    // Takes every scene off the stack, e.g. when the game ends
+    pub fn clear(&mut self, state: &mut GameState<'ttf>) {
+        while self.remove_top(state) {}
+        self.update_input_contexts(state);
+    }
+
+    // Contexts layer from the top scene down, a scene without one (e.g. a menu) hides those beneath it
+    fn update_input_contexts(&self, state: &mut GameState<'ttf>) {
+        let mut contexts: Vec<String> = self.stack.iter().rev()
+            .map_while(|entry| entry.scene.input_context())
+            .map(|name| name.to_owned())
+            .collect();
+        contexts.reverse();
+        state.input_contexts = contexts;
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    // Scene::name of the scene on top
+    pub fn top_name(&self) -> Option<&'static str> {
+        self.stack.last().map(|entry| entry.scene.name())
+    }
+
+    // Runs one frame without drawing: a batch of input, the updates, then the game events
+    pub fn frame<I: InputSource>(&mut self, state: &mut GameState<'ttf>, input: &mut I) {
+        input.process_events(state, self);
+        self.update(state, input.input());
+        EventBus::dispatch(state);
+    }
+
+    fn process(&mut self, state: &mut GameState<'ttf>, result: Option<EventResult<'ttf, T>>) {
+        match result {
+            Some(EventResult::PushScene(scene)) => self.push(state, scene, false, false),
+            Some(EventResult::PushOverlay { scene, update_below }) => self.push(state, scene, true, update_below),
+            Some(EventResult::PopScene) => self.pop(state, 1),
+            Some(EventResult::ReplaceScene(mut scene)) => {
+                self.remove_top(state);
+                scene.on_enter(state);
+                self.stack.push(StackEntry { scene, overlay: false, update_below: false });
+            }
+            Some(EventResult::PopTo(name)) => match self.stack.iter().rposition(|entry| entry.scene.name() == name) {
+                Some(index) => self.pop(state, self.stack.len() - index - 1),
+                None => println!("No scene {} to go back to", name),
+            },
+            Some(EventResult::ClearAndPush(mut scene)) => {
+                self.clear(state);
+                scene.on_enter(state);
+                self.stack.push(StackEntry { scene, overlay: false, update_below: false });
+            }
+            Some(EventResult::Quit) => state.running = false,
+            Some(EventResult::Consumed) | None => return,
+        };
+        // Nothing left to show
+        if self.stack.is_empty() {
+            state.running = false;
+        }
+        self.update_input_contexts(state);
+    }
+
+    fn active_scene_mut(&mut self) -> Option<&mut (dyn Scene<'ttf, T> + 'ttf)> {
+        self.stack.last_mut().map(|entry| entry.scene.as_mut())
+    }
+
+    // Index of the lowest scene that is drawn: overlays show what is beneath them
+    fn first_visible(&self) -> usize {
+        self.stack.iter().rposition(|entry| !entry.overlay).unwrap_or(0)
+    }
+
+    // Index of the lowest scene that is updated: overlays pushed with update_below let the scenes beneath keep running
+    fn first_updated(&self) -> usize {
+        self.stack.iter().rposition(|entry| !entry.overlay || !entry.update_below).unwrap_or(0)
+    }
+}
+
+impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for SceneStack<'ttf, T> {
+    fn batch_start(&mut self, state: &mut GameState<'ttf>, input: &InputState) -> Option<EventResult<'ttf, T>> {
+        for index in 0..self.global_listeners.len() {
+            let result = self.global_listeners[index].batch_start(state, input);
+            self.process(state, result);
+        }
+        let result = self.active_scene_mut()?.batch_start(state, input);
+        self.process(state, result);
+        None
+    }
+
+    fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
+        for index in 0..self.global_listeners.len() {
+            let result = self.global_listeners[index].process_event(state, event);
+            if result.is_some() {
+                self.process(state, result);
+                return None;
+            }
+        }
+        let result = self.active_scene_mut()?.process_event(state, event);
+        self.process(state, result);
+        None
+    }
+
+    fn batch_end(&mut self, state: &mut GameState<'ttf>, input: &InputState) -> Option<EventResult<'ttf, T>> {
+        for index in 0..self.global_listeners.len() {
+            let result = self.global_listeners[index].batch_end(state, input);
+            self.process(state, result);
+        }
+        let result = self.active_scene_mut()?.batch_end(state, input);
+        self.process(state, result);
+        None
+    }
+}
+
+impl<'ttf, T: RenderTarget> Scene<'ttf, T> for SceneStack<'ttf, T> {
+    fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error> {
+        let first = self.first_visible();
+        for entry in self.stack.iter_mut().skip(first) {
+            entry.scene.draw(renderer, resources)?;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, state: &mut GameState<'ttf>, input: &InputState) -> Option<EventResult<'ttf, T>> {
+        // Scenes beneath the top one only animate: they see nothing pressed and what they return is dropped
+        let top = self.stack.len().checked_sub(1)?;
+        let nothing_pressed = InputState::default();
+        for index in self.first_updated()..top {
+            self.stack[index].scene.update(state, &nothing_pressed);
+        }
+        let result = self.active_scene_mut()?.update(state, input);
+        self.process(state, result);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use sdl2::pixels::PixelFormatEnum;
+    use sdl2::surface::Surface;
+
+    use crate::data::{self, GameConfig};
+    use crate::gfx::font::BitmapFont;
+    use crate::input_source::ScriptedInput;
+    use crate::keymap::Action;
+    use crate::scene::main_menu::{MainMenu, MAIN_MENU};
+    use crate::scene::text_input::TEXT_INPUT;
+
+    use super::*;
+
+    fn main_menu_stack(state: &mut GameState<'static>) -> SceneStack<'static, Surface<'static>> {
+        let config: GameConfig = data::load_file("data/config.json").unwrap();
+        let font = Rc::new(BitmapFont::new(Vec::new(), HashMap::new(), HashMap::new(), 16));
+        let mut scene_stack = SceneStack::new(Vec::new());
+        scene_stack.push(state, Box::new(MainMenu::new(font, None, config.map)), false, false);
+        scene_stack
+    }
+
+    fn run(scene_stack: &mut SceneStack<'static, Surface<'static>>, state: &mut GameState<'static>, input: &mut ScriptedInput) {
+        while !input.is_finished() {
+            scene_stack.frame(state, input);
+        }
+    }
+
+    struct ContextScene(Option<&'static str>);
+
+    impl EventListener<'static, Surface<'static>> for ContextScene {}
+
+    impl Scene<'static, Surface<'static>> for ContextScene {
+        fn draw(&mut self, _renderer: &mut Renderer<Surface<'static>>, _resources: &mut dyn Resources<'static>) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn input_context(&self) -> Option<&str> {
+            self.0
+        }
+    }
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    // Writes "<name> <call>" to the log for every call it gets
+    struct LogScene {
+        name: &'static str,
+        log: Log,
+    }
+
+    impl LogScene {
+        fn boxed(name: &'static str, log: &Log) -> Box<dyn Scene<'static, Surface<'static>>> {
+            Box::new(LogScene { name, log: log.clone() })
+        }
+
+        fn write(&self, call: &str) {
+            self.log.borrow_mut().push(format!("{} {}", self.name, call));
+        }
+    }
+
+    impl EventListener<'static, Surface<'static>> for LogScene {
+        fn process_event(&mut self, _state: &mut GameState<'static>, event: &Event) -> Option<EventResult<'static, Surface<'static>>> {
+            self.write(&format!("{:?}", event));
+            None
+        }
+    }
+
+    impl Scene<'static, Surface<'static>> for LogScene {
+        fn draw(&mut self, _renderer: &mut Renderer<Surface<'static>>, _resources: &mut dyn Resources<'static>) -> Result<(), Error> {
+            self.write("draw");
+            Ok(())
+        }
+
+        fn update(&mut self, _state: &mut GameState<'static>, _input: &InputState) -> Option<EventResult<'static, Surface<'static>>> {
+            self.write("update");
+            None
+        }
+
+        fn on_enter(&mut self, _state: &mut GameState<'static>) { self.write("enter") }
+        fn on_exit(&mut self, _state: &mut GameState<'static>) { self.write("exit") }
+        fn on_pause(&mut self, _state: &mut GameState<'static>) { self.write("pause") }
+        fn on_resume(&mut self, _state: &mut GameState<'static>) { self.write("resume") }
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+    }
+
+    fn names(scene_stack: &SceneStack<'static, Surface<'static>>) -> Vec<&'static str> {
+        scene_stack.stack.iter().map(|entry| entry.scene.name()).collect()
+    }
+
+    // Takes what was logged since the last call
+    fn take(log: &Log) -> Vec<String> {
+        log.borrow_mut().drain(..).collect()
+    }
+
+    #[test]
+    fn transitions() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        let mut scene_stack = SceneStack::new(Vec::new());
+        scene_stack.push(&mut state, LogScene::boxed("title", &log), false, false);
+        scene_stack.process(&mut state, Some(EventResult::PushScene(LogScene::boxed("map", &log))));
+        scene_stack.process(&mut state, Some(EventResult::PushOverlay { scene: LogScene::boxed("menu", &log), update_below: true }));
+
+        scene_stack.process(&mut state, Some(EventResult::ReplaceScene(LogScene::boxed("settings", &log))));
+        assert_eq!(names(&scene_stack), ["title", "map", "settings"]);
+        // The replacement is not an overlay, the map is hidden
+        assert_eq!(scene_stack.first_visible(), 2);
+
+        // Missing scenes leave the stack alone
+        scene_stack.process(&mut state, Some(EventResult::PopTo("credits")));
+        assert_eq!(names(&scene_stack), ["title", "map", "settings"]);
+        scene_stack.process(&mut state, Some(EventResult::PopTo("title")));
+        assert_eq!(names(&scene_stack), ["title"]);
+        // Already on top
+        scene_stack.process(&mut state, Some(EventResult::PopTo("title")));
+        assert_eq!(names(&scene_stack), ["title"]);
+
+        scene_stack.process(&mut state, Some(EventResult::PushScene(LogScene::boxed("map", &log))));
+        scene_stack.process(&mut state, Some(EventResult::ClearAndPush(LogScene::boxed("game over", &log))));
+        assert_eq!(names(&scene_stack), ["game over"]);
+        assert!(state.running);
+    }
+
+    #[test]
+    fn lifecycle_hooks() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        let mut scene_stack = SceneStack::new(Vec::new());
+        scene_stack.push(&mut state, LogScene::boxed("title", &log), false, false);
+        assert_eq!(take(&log), ["title enter"]);
+        scene_stack.process(&mut state, Some(EventResult::PushScene(LogScene::boxed("map", &log))));
+        assert_eq!(take(&log), ["title pause", "map enter"]);
+        // Overlays pause the scene beneath too, even when it keeps updating
+        scene_stack.process(&mut state, Some(EventResult::PushOverlay { scene: LogScene::boxed("menu", &log), update_below: true }));
+        assert_eq!(take(&log), ["map pause", "menu enter"]);
+        scene_stack.process(&mut state, Some(EventResult::PopScene));
+        assert_eq!(take(&log), ["menu exit", "map resume"]);
+
+        // Only the new top resumes after several pops
+        scene_stack.push(&mut state, LogScene::boxed("menu", &log), true, false);
+        scene_stack.push(&mut state, LogScene::boxed("settings", &log), false, false);
+        take(&log);
+        scene_stack.pop(&mut state, 2);
+        assert_eq!(take(&log), ["settings exit", "menu exit", "map resume"]);
+
+        // The scene beneath a replaced one is neither paused nor resumed
+        scene_stack.process(&mut state, Some(EventResult::ReplaceScene(LogScene::boxed("cave", &log))));
+        assert_eq!(take(&log), ["map exit", "cave enter"]);
+        scene_stack.process(&mut state, Some(EventResult::PopTo("title")));
+        assert_eq!(take(&log), ["cave exit", "title resume"]);
+        scene_stack.process(&mut state, Some(EventResult::PushScene(LogScene::boxed("map", &log))));
+        take(&log);
+        scene_stack.process(&mut state, Some(EventResult::ClearAndPush(LogScene::boxed("game over", &log))));
+        assert_eq!(take(&log), ["map exit", "title exit", "game over enter"]);
+        scene_stack.clear(&mut state);
+        assert_eq!(take(&log), ["game over exit"]);
+    }
+
+    // Global listener that consumes Select, and pushes a scene at the start of its first batch
+    struct Consumer {
+        log: Log,
+        push: Option<&'static str>,
+    }
+
+    impl EventListener<'static, Surface<'static>> for Consumer {
+        fn batch_start(&mut self, _state: &mut GameState<'static>, _input: &InputState) -> Option<EventResult<'static, Surface<'static>>> {
+            Some(EventResult::PushScene(LogScene::boxed(self.push.take()?, &self.log)))
+        }
+
+        fn process_event(&mut self, _state: &mut GameState<'static>, event: &Event) -> Option<EventResult<'static, Surface<'static>>> {
+            match event {
+                Event::ActionDown { action: Action::Select } => Some(EventResult::Consumed),
+                _ => None,
+            }
+        }
+    }
+
+    // Global listener that logs what it sees, and pushes a scene at the end of its first batch
+    struct Watcher {
+        log: Log,
+        push: Option<&'static str>,
+    }
+
+    impl EventListener<'static, Surface<'static>> for Watcher {
+        fn process_event(&mut self, _state: &mut GameState<'static>, event: &Event) -> Option<EventResult<'static, Surface<'static>>> {
+            self.log.borrow_mut().push(format!("watcher {:?}", event));
+            None
+        }
+
+        fn batch_end(&mut self, _state: &mut GameState<'static>, _input: &InputState) -> Option<EventResult<'static, Surface<'static>>> {
+            Some(EventResult::PushScene(LogScene::boxed(self.push.take()?, &self.log)))
+        }
+    }
+
+    #[test]
+    fn global_listeners_come_first() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        let consumer = Consumer { log: log.clone(), push: Some("first") };
+        let watcher = Watcher { log: log.clone(), push: Some("second") };
+        let mut scene_stack = SceneStack::new(vec![Box::new(consumer), Box::new(watcher)]);
+        scene_stack.push(&mut state, LogScene::boxed("title", &log), false, false);
+        take(&log);
+
+        let mut input = ScriptedInput::new();
+        input.batch(vec![Event::ActionDown { action: Action::Select }, Event::ActionDown { action: Action::Down }], &[Action::Down]);
+        scene_stack.frame(&mut state, &mut input);
+        // Batch results are all processed, in order. The consumed event reaches neither the watcher nor the scene.
+        assert_eq!(names(&scene_stack), ["title", "first", "second"]);
+        let down = format!("{:?}", Event::ActionDown { action: Action::Down });
+        assert_eq!(take(&log), [
+            "title pause".to_owned(),
+            "first enter".to_owned(),
+            format!("watcher {}", down),
+            format!("first {}", down),
+            "first pause".to_owned(),
+            "second enter".to_owned(),
+            "second update".to_owned(),
+        ]);
+    }
+
+    #[test]
+    fn popping_the_last_scene_quits() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        let mut scene_stack = SceneStack::new(Vec::new());
+        scene_stack.push(&mut state, LogScene::boxed("title", &log), false, false);
+        scene_stack.process(&mut state, Some(EventResult::PopScene));
+        assert!(scene_stack.is_empty());
+        assert!(!state.running);
+        // Nothing left to pop, draw or update
+        scene_stack.process(&mut state, Some(EventResult::PopScene));
+        scene_stack.frame(&mut state, ScriptedInput::new().tap(Action::Select));
+        assert!(scene_stack.is_empty());
+    }
+
+    #[test]
+    fn overlays_draw_and_update_what_is_beneath() {
+        let mut state = GameState::headless();
+        let log = Log::default();
+        let mut canvas = Surface::new(8, 8, PixelFormatEnum::RGBA32).unwrap().into_canvas().unwrap();
+        let mut scene_stack = SceneStack::new(Vec::new());
+        scene_stack.push(&mut state, LogScene::boxed("title", &log), false, false);
+        scene_stack.push(&mut state, LogScene::boxed("map", &log), false, false);
+        scene_stack.push(&mut state, LogScene::boxed("menu", &log), true, true);
+        take(&log);
+
+        let mut draw_and_update = |scene_stack: &mut SceneStack<'static, Surface<'static>>, state: &mut GameState<'static>| {
+            scene_stack.draw(&mut Renderer::new(&mut canvas), state.resources.as_mut()).unwrap();
+            scene_stack.update(state, &InputState::default());
+            take(&log)
+        };
+        // The map is not an overlay, the title beneath it is neither drawn nor updated
+        assert_eq!(draw_and_update(&mut scene_stack, &mut state), ["map draw", "menu draw", "map update", "menu update"]);
+
+        // Scenes under an overlay without update_below only get drawn
+        scene_stack.push(&mut state, LogScene::boxed("dialog", &log), true, false);
+        take(&log);
+        assert_eq!(draw_and_update(&mut scene_stack, &mut state), ["map draw", "menu draw", "dialog draw", "dialog update"]);
+    }
+
+    #[test]
+    fn input_contexts_follow_pushes() {
+        let mut state = GameState::headless();
+        let mut scene_stack: SceneStack<Surface<'static>> = SceneStack::new(Vec::new());
+        scene_stack.push(&mut state, Box::new(ContextScene(Some("map"))), false, false);
+        assert_eq!(state.input_contexts, ["map"]);
+        scene_stack.push(&mut state, Box::new(ContextScene(Some("dialog"))), true, false);
+        assert_eq!(state.input_contexts, ["map", "dialog"]);
+        // A scene without a context hides the ones beneath
+        scene_stack.push(&mut state, Box::new(ContextScene(None)), true, false);
+        assert!(state.input_contexts.is_empty());
+        scene_stack.pop(&mut state, 2);
+        assert_eq!(state.input_contexts, ["map"]);
+        scene_stack.clear(&mut state);
+        assert!(state.input_contexts.is_empty());
+    }
+
+    #[test]
+    fn select_and_back() {
+        let mut state = GameState::headless();
+        let mut scene_stack = main_menu_stack(&mut state);
+        assert_eq!(scene_stack.top_name(), Some(MAIN_MENU));
+
+        run(&mut scene_stack, &mut state, ScriptedInput::new().tap(Action::Select));
+        assert_eq!(scene_stack.top_name(), Some(TEXT_INPUT));
+        assert!(state.text_input);
+
+        // Back cancels the empty name
+        run(&mut scene_stack, &mut state, ScriptedInput::new().tap(Action::Back));
+        assert_eq!(scene_stack.top_name(), Some(MAIN_MENU));
+        assert!(!state.text_input);
+        assert!(state.running);
+    }
+
+    #[test]
+    fn back_on_main_menu_quits() {
+        let mut state = GameState::headless();
+        let mut scene_stack = main_menu_stack(&mut state);
+        run(&mut scene_stack, &mut state, ScriptedInput::new().tap(Action::Back));
+        assert!(!state.running);
+    }
+
+    #[test]
+    fn quit_option_quits() {
+        let mut state = GameState::headless();
+        let mut scene_stack = main_menu_stack(&mut state);
+        run(&mut scene_stack, &mut state, ScriptedInput::new().tap(Action::Down).tap(Action::Down).wait());
+        assert!(state.running);
+        run(&mut scene_stack, &mut state, ScriptedInput::new().tap(Action::Select));
+        assert!(!state.running);
+        assert_eq!(scene_stack.top_name(), Some(MAIN_MENU));
+    }
+}
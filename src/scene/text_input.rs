@@ -0,0 +1,301 @@
+use std::rc::Rc;
+
+use sdl2::event::Event as SdlEvent;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::RenderTarget;
+
+use crate::{Error, Event, EventListener, EventResult, GameState, InputState, Resources, Scene};
+use crate::gfx::font::TextFont;
+use crate::gfx::renderer::Renderer;
+use crate::gfx::window::WindowSkin;
+use crate::keymap::Action;
+use crate::point::IntPoint;
+
+const POSITION: IntPoint = IntPoint { x: 200, y: 120 };
+const LINE_HEIGHT: i32 = 50;
+const WINDOW_PADDING: i32 = 16;
+const CELL_SIZE: i32 = 44;
+const GRID_TOP: i32 = LINE_HEIGHT * 3;
+const FIELD_WIDTH: u32 = 572;
+const CARET_BLINK: u32 = 500;
+const CHARACTER_ROWS: [&str; 5] = ["ABCDEFGHIJKLM", "NOPQRSTUVWXYZ", "abcdefghijklm", "nopqrstuvwxyz", "0123456789 -."];
+// The delete and done cells are this many characters wide
+const WIDE_CELL: i32 = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum GridCell {
+    Character(char),
+    Delete,
+    Done,
+}
+
+impl GridCell {
+    fn label(&self) -> String {
+        match self {
+            GridCell::Character(' ') => "_".to_owned(),
+            GridCell::Character(c) => c.to_string(),
+            GridCell::Delete => "Del".to_owned(),
+            GridCell::Done => "OK".to_owned(),
+        }
+    }
+
+    fn width(&self) -> i32 {
+        match self {
+            GridCell::Character(_) => 1,
+            _ => WIDE_CELL,
+        }
+    }
+}
+
+// Text typed in a TextInputScene, kept in the game state until the scene that asked for it takes it
+pub struct TextInputResult {
+    pub id: &'static str,
+    pub text: String,
+}
+
+// Asks for a line of text. Keyboards type into it directly (SDL text input, so IMEs work),
+// controllers and mice pick characters from a grid. Confirming stores the text in the game state
+// under the given id and pops the scene, cancelling pops it without a result.
+pub struct TextInputScene<'ttf> {
+    font: Rc<dyn TextFont<'ttf> + 'ttf>,
+    window_skin: Option<Rc<WindowSkin<'ttf>>>,
+    id: &'static str,
+    prompt: String,
+    text: Vec<char>,
+    max_length: usize,
+    // Position of the caret in characters
+    caret: usize,
+    // Text being composed by an input method, not part of the text yet
+    composition: String,
+    grid: Vec<Vec<GridCell>>,
+    grid_cursor: IntPoint,
+    ticks: u32,
+    // Top left of the scene on screen at the last draw, for mouse hit tests
+    origin: IntPoint,
+}
+
+impl<'ttf> TextInputScene<'ttf> {
+    pub fn new(font: Rc<dyn TextFont<'ttf> + 'ttf>, window_skin: Option<Rc<WindowSkin<'ttf>>>, id: &'static str, prompt: &str, initial: &str, max_length: usize) -> Self {
+        let mut grid: Vec<Vec<GridCell>> = CHARACTER_ROWS.iter()
+            .map(|row| row.chars().map(GridCell::Character).collect())
+            .collect();
+        grid.push(vec![GridCell::Delete, GridCell::Done]);
+        let text: Vec<char> = initial.chars().take(max_length).collect();
+        TextInputScene {
+            font,
+            window_skin,
+            id,
+            prompt: prompt.to_owned(),
+            caret: text.len(),
+            text,
+            max_length,
+            composition: String::new(),
+            grid,
+            grid_cursor: IntPoint::new(0, 0),
+            ticks: 0,
+            origin: POSITION,
+        }
+    }
+
+    fn insert(&mut self, input: &str) {
+        for c in input.chars().filter(|c| !c.is_control()) {
+            if self.text.len() >= self.max_length {
+                break;
+            }
+            self.text.insert(self.caret, c);
+            self.caret += 1;
+        }
+        self.ticks = 0;
+    }
+
+    fn backspace(&mut self) {
+        if self.caret > 0 {
+            self.caret -= 1;
+            self.text.remove(self.caret);
+        }
+        self.ticks = 0;
+    }
+
+    fn move_caret(&mut self, delta: i32) {
+        self.caret = (self.caret as i32 + delta).clamp(0, self.text.len() as i32) as usize;
+        self.ticks = 0;
+    }
+
+    fn move_grid_cursor(&mut self, dx: i32, dy: i32) {
+        let rows = self.grid.len() as i32;
+        let y = (self.grid_cursor.y + dy).rem_euclid(rows);
+        let columns = self.grid[y as usize].len() as i32;
+        let x = if dy != 0 { self.grid_cursor.x.min(columns - 1) } else { (self.grid_cursor.x + dx).rem_euclid(columns) };
+        self.grid_cursor = IntPoint::new(x, y);
+    }
+
+    // Cell rectangle relative to the scene origin
+    fn cell_rect(&self, x: usize, y: usize) -> Rect {
+        let left: i32 = self.grid[y][..x].iter().map(|cell| cell.width()).sum();
+        let width = self.grid[y][x].width() * CELL_SIZE;
+        Rect::new(left * CELL_SIZE, GRID_TOP + y as i32 * CELL_SIZE, width as u32, CELL_SIZE as u32)
+    }
+
+    fn cell_at(&self, position: IntPoint) -> Option<IntPoint> {
+        let local = position - self.origin;
+        (0..self.grid.len())
+            .flat_map(|y| (0..self.grid[y].len()).map(move |x| (x, y)))
+            .find(|(x, y)| self.cell_rect(*x, *y).contains_point((local.x, local.y)))
+            .map(|(x, y)| IntPoint::new(x as i32, y as i32))
+    }
+
+    fn press_cell<T: RenderTarget>(&mut self, state: &mut GameState<'ttf>) -> Option<EventResult<'ttf, T>> {
+        match self.grid[self.grid_cursor.y as usize][self.grid_cursor.x as usize] {
+            GridCell::Character(c) => self.insert(&c.to_string()),
+            GridCell::Delete => self.backspace(),
+            GridCell::Done => return self.finish(state, true),
+        }
+        None
+    }
+
+    fn finish<T: RenderTarget>(&mut self, state: &mut GameState<'ttf>, confirmed: bool) -> Option<EventResult<'ttf, T>> {
+        if confirmed {
+            state.text_input_result = Some(TextInputResult { id: self.id, text: self.text.iter().collect() });
+        }
+        Some(EventResult::PopScene)
+    }
+
+    // Keys come in raw while the scene is on top, so typing a letter bound to an action does not
+    // also trigger it. Controllers still send actions to move around the grid.
+    fn take_keyboard(&self, state: &mut GameState<'ttf>, active: bool) {
+        state.text_input = active;
+        state.raw_keys = active;
+    }
+}
+
+impl<'ttf, T: RenderTarget> EventListener<'ttf, T> for TextInputScene<'ttf> {
+    fn process_event(&mut self, state: &mut GameState<'ttf>, event: &Event) -> Option<EventResult<'ttf, T>> {
+        match event {
+            Event::Sdl(SdlEvent::TextInput { text, .. }) => {
+                self.composition.clear();
+                self.insert(text);
+            }
+            Event::Sdl(SdlEvent::TextEditing { text, .. }) => self.composition = text.clone(),
+            Event::Sdl(SdlEvent::KeyDown { keycode: Some(keycode), .. }) => match *keycode {
+                Keycode::Backspace => self.backspace(),
+                Keycode::Left => self.move_caret(-1),
+                Keycode::Right => self.move_caret(1),
+                Keycode::Home => self.caret = 0,
+                Keycode::End => self.caret = self.text.len(),
+                Keycode::Return | Keycode::KpEnter => return self.finish(state, true),
+                Keycode::Escape => return self.finish(state, false),
+                _ => {}
+            },
+            Event::ActionDown { action } | Event::ActionRepeat { action } => match action {
+                Action::Up => self.move_grid_cursor(0, -1),
+                Action::Down => self.move_grid_cursor(0, 1),
+                Action::Left => self.move_grid_cursor(-1, 0),
+                Action::Right => self.move_grid_cursor(1, 0),
+                Action::Select => return self.press_cell(state),
+                // Back deletes first and only cancels an empty text
+                Action::Back if self.text.is_empty() => return self.finish(state, false),
+                Action::Back => self.backspace(),
+                _ => {}
+            },
+            Event::MouseMove { position } => {
+                if let Some(cell) = self.cell_at(*position) {
+                    self.grid_cursor = cell;
+                }
+            }
+            Event::MouseDown { button: MouseButton::Left, position } => {
+                if let Some(cell) = self.cell_at(*position) {
+                    self.grid_cursor = cell;
+                    return self.press_cell(state);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+pub const TEXT_INPUT: &str = "text input";
+
+impl<'ttf, T: RenderTarget> Scene<'ttf, T> for TextInputScene<'ttf> {
+    fn name(&self) -> &'static str { TEXT_INPUT }
+
+    fn on_enter(&mut self, state: &mut GameState<'ttf>) {
+        self.take_keyboard(state, true);
+    }
+
+    fn on_exit(&mut self, state: &mut GameState<'ttf>) {
+        self.take_keyboard(state, false);
+    }
+
+    fn on_pause(&mut self, state: &mut GameState<'ttf>) {
+        self.take_keyboard(state, false);
+    }
+
+    fn on_resume(&mut self, state: &mut GameState<'ttf>) {
+        self.take_keyboard(state, true);
+    }
+
+    fn draw(&mut self, renderer: &mut Renderer<T>, resources: &mut dyn Resources<'ttf>) -> Result<(), Error> {
+        self.origin = renderer.offset() + POSITION;
+        renderer.with_offset(POSITION, |renderer| {
+            if let Some(skin) = &self.window_skin {
+                let height = GRID_TOP + self.grid.len() as i32 * CELL_SIZE + WINDOW_PADDING;
+                skin.draw_window(renderer, Rect::new(-WINDOW_PADDING, -WINDOW_PADDING / 2, FIELD_WIDTH + 2 * WINDOW_PADDING as u32, height as u32))?;
+            }
+            if !self.prompt.is_empty() {
+                self.font.render(&self.prompt, Color::WHITE, resources)?.draw(renderer, IntPoint::new(0, 0))?;
+            }
+
+            let field = Rect::new(0, LINE_HEIGHT, FIELD_WIDTH, LINE_HEIGHT as u32);
+            renderer.set_draw_color(Color::WHITE);
+            renderer.draw_rect(field)?;
+            let before: String = self.text[..self.caret].iter().collect();
+            let after: String = self.text[self.caret..].iter().collect();
+            let text_x = 8;
+            let caret_x = text_x + if before.is_empty() { 0 } else { self.font.size_of(&before)?.0 as i32 };
+            let mut x = text_x;
+            for (part, color) in [(&before, Color::WHITE), (&self.composition, Color::GRAY), (&after, Color::WHITE)] {
+                if !part.is_empty() {
+                    let rendered = self.font.render(part, color, resources)?;
+                    rendered.draw(renderer, IntPoint::new(x, LINE_HEIGHT))?;
+                    x += rendered.width() as i32;
+                }
+            }
+            if (self.ticks / CARET_BLINK).is_multiple_of(2) {
+                renderer.set_draw_color(Color::WHITE);
+                renderer.fill_rect(Rect::new(caret_x, LINE_HEIGHT + 6, 2, LINE_HEIGHT as u32 - 12))?;
+            }
+            let counter = format!("{}/{}", self.text.len(), self.max_length);
+            let counter_width = self.font.size_of(&counter)?.0 as i32;
+            self.font.render(&counter, Color::GRAY, resources)?.draw(renderer, IntPoint::new(FIELD_WIDTH as i32 - counter_width, 0))?;
+
+            for (y, row) in self.grid.iter().enumerate() {
+                for (x, cell) in row.iter().enumerate() {
+                    let rect = self.cell_rect(x, y);
+                    let selected = self.grid_cursor == IntPoint::new(x as i32, y as i32);
+                    if selected {
+                        match &self.window_skin {
+                            Some(skin) => skin.draw_cursor(renderer, rect)?,
+                            None => {
+                                renderer.set_draw_color(Color::RED);
+                                renderer.draw_rect(rect)?;
+                            }
+                        }
+                    }
+                    let label = self.font.render(&cell.label(), if selected { Color::RED } else { Color::WHITE }, resources)?;
+                    let center = IntPoint::new(rect.x() + (rect.width() as i32 - label.width() as i32) / 2, rect.y() + (rect.height() as i32 - label.height() as i32) / 2);
+                    label.draw(renderer, center)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn update(&mut self, state: &mut GameState<'ttf>, _input: &InputState) -> Option<EventResult<'ttf, T>> {
+        self.ticks = self.ticks.wrapping_add(state.ticks_to_process);
+        None
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::data;
+use crate::data::settings::{ContextKeyMapData, GamepadMapData, HotkeyMapData, KeyMapData, SettingsData};
+use crate::error::Error;
+use crate::gamepad::GamepadMap;
+use crate::hotkeys::HotkeyMap;
+use crate::input_context::{ActionResolver, default_contexts, InputContext};
+use crate::keymap::{Action, KeyMap};
+use crate::repeat::RepeatConfig;
+
+pub struct Settings {
+    // Where save writes to, settings without a path are only kept in memory
+    path: Option<PathBuf>,
+    pub key_map: Rc<KeyMap>,
+    pub gamepad_map: Rc<GamepadMap>,
+    pub repeat: RepeatConfig,
+    pub contexts: Vec<InputContext>,
+    pub hotkeys: Rc<HotkeyMap>,
+}
+
+impl Settings {
+    pub fn new(key_map: KeyMap, gamepad_map: GamepadMap, path: Option<PathBuf>) -> Self {
+        Settings { path, key_map: Rc::new(key_map), gamepad_map: Rc::new(gamepad_map), repeat: RepeatConfig::default(), contexts: default_contexts(), hotkeys: Rc::new(HotkeyMap::default()) }
+    }
+
+    // Missing or broken settings files fall back to the defaults, so a bad file can not keep the game from starting
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let mut settings = Settings::new(KeyMap::default(), GamepadMap::default(), Some(path.to_owned()));
+        if path.exists() {
+            if let Err(e) = data::load_file::<_, SettingsData>(path).and_then(|data| settings.apply(&data)) {
+                println!("Could not load settings, using defaults: {:?}", e);
+                settings = Settings::new(KeyMap::default(), GamepadMap::default(), Some(path.to_owned()));
+            }
+        }
+        settings
+    }
+
+    fn apply(&mut self, data: &SettingsData) -> Result<(), Error> {
+        self.key_map = Rc::new(data.key_map.to_key_map(&KeyMap::default())?);
+        self.gamepad_map = Rc::new(data.gamepad_map.to_gamepad_map()?);
+        self.repeat = data.repeat;
+        self.hotkeys = Rc::new(data.hotkeys.to_hotkey_map()?);
+        for context_data in &data.contexts {
+            match self.contexts.iter_mut().find(|context| context.name == context_data.name) {
+                Some(context) => context.key_map = Rc::new(context_data.key_map.to_key_map(&context.key_map)?),
+                None => println!("Ignoring bindings for unknown input context {}", context_data.name),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_key_map(&mut self, key_map: KeyMap) {
+        self.key_map = Rc::new(key_map);
+    }
+
+    pub fn context(&self, name: &str) -> Option<&InputContext> {
+        self.contexts.iter().find(|context| context.name == name)
+    }
+
+    pub fn set_context_key_map(&mut self, name: &str, key_map: KeyMap) {
+        if let Some(context) = self.contexts.iter_mut().find(|context| context.name == name) {
+            context.key_map = Rc::new(key_map);
+        }
+    }
+
+    // Puts every key map back to its defaults
+    pub fn reset_key_maps(&mut self) {
+        self.key_map = Rc::new(KeyMap::default());
+        self.contexts = default_contexts();
+    }
+
+    // Resolves keys for the given active context names, bottom first like the scene stack
+    pub fn resolver(&self, active: &[String]) -> ActionResolver<'_> {
+        let contexts = active.iter().rev().filter_map(|name| self.context(name)).collect();
+        ActionResolver::new(&self.key_map, contexts, &self.hotkeys)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        match &self.path {
+            Some(path) => data::write_file(path, &SettingsData {
+                key_map: KeyMapData::from_key_map(&self.key_map, &Action::BASE),
+                gamepad_map: GamepadMapData::from_gamepad_map(&self.gamepad_map),
+                repeat: self.repeat,
+                contexts: self.contexts.iter()
+                    .map(|context| ContextKeyMapData { name: context.name.clone(), key_map: KeyMapData::from_key_map(&context.key_map, &context.actions) })
+                    .collect(),
+                hotkeys: HotkeyMapData::from_hotkey_map(&self.hotkeys),
+            }),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::sync::{Mutex, MutexGuard};
+
+use sdl2::Sdl;
+
+// Only one SDL context can exist at a time, so tests that need SDL take turns
+static SDL_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct TestSdl {
+    pub sdl: Sdl,
+    // Declared last so it is released once SDL is shut down
+    _lock: MutexGuard<'static, ()>,
+}
+
+// Starts SDL for a test, without a display unless SDL_VIDEODRIVER says otherwise
+pub fn init() -> TestSdl {
+    // A test that failed while holding the lock does not stop the others
+    let lock = SDL_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if std::env::var_os("SDL_VIDEODRIVER").is_none() {
+        std::env::set_var("SDL_VIDEODRIVER", "dummy");
+    }
+    TestSdl { sdl: sdl2::init().unwrap(), _lock: lock }
+}